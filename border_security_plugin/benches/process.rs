@@ -0,0 +1,76 @@
+//! Compares the block based processing with smoothed parameters against the old per sample
+//! `.value()` calls, using 16 buckets. Run with `cargo bench --features bench`.
+
+use border_security_plugin::BorderSecurityPlugin;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use nih_plug::prelude::*;
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const NUM_CHANNELS: usize = 2;
+const BLOCK_SIZE: usize = 512;
+
+/// A stereo block of noise-like audio, so the gates see a spread of amplitudes.
+fn input() -> Vec<Vec<f32>> {
+    let mut state = 0x9e37_79b9u32;
+    (0..NUM_CHANNELS)
+        .map(|_| {
+            (0..BLOCK_SIZE)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as f32 / u32::MAX as f32 * 2.0 - 1.0
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn bench_process(
+    c: &mut Criterion,
+    name: &str,
+    process: fn(&mut BorderSecurityPlugin, &mut Buffer) -> ProcessStatus,
+) {
+    let input = input();
+    let mut channels = input.clone();
+    let mut buffer = Buffer::default();
+    // SAFETY: `channels` outlives `buffer` and isn't accessed directly while `buffer` exists
+    unsafe {
+        buffer.set_slices(BLOCK_SIZE, |output_slices| {
+            *output_slices = channels
+                .iter_mut()
+                .map(|channel| channel.as_mut_slice())
+                .collect();
+        });
+    }
+
+    let mut plugin = BorderSecurityPlugin::default();
+    plugin.resize(NUM_CHANNELS, SAMPLE_RATE);
+    plugin.reset();
+
+    let mut group = c.benchmark_group("process");
+    group.throughput(Throughput::Elements((BLOCK_SIZE * NUM_CHANNELS) as u64));
+    group.bench_function(name, |b| {
+        b.iter(|| {
+            // The buffer is processed in place, so it's refilled to keep the input the same
+            for (channel, input) in buffer.as_slice().iter_mut().zip(&input) {
+                channel.copy_from_slice(input);
+            }
+
+            process(&mut plugin, &mut buffer)
+        })
+    });
+    group.finish();
+}
+
+fn compare_processing(c: &mut Criterion) {
+    bench_process(c, "smoothed blocks", BorderSecurityPlugin::process_buffer);
+    bench_process(
+        c,
+        "per sample value()",
+        BorderSecurityPlugin::process_buffer_per_sample,
+    );
+}
+
+criterion_group!(benches, compare_processing);
+criterion_main!(benches);
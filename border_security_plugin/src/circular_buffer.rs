@@ -1,59 +1,486 @@
 use nih_plug::prelude::*;
+use std::marker::PhantomData;
 
-pub struct CircleBuffer {
-    sample_rate: f32,
+/// A ring buffer holding the most recently written samples.
+///
+/// Reads are addressed in samples ago: an offset of `0` is the sample that was written last, and
+/// `num_samples - 1` is the oldest sample that's still stored in the buffer. Offsets past the
+/// oldest sample are clamped to that oldest sample instead of wrapping around, since wrapping would
+/// silently turn a too long delay into a very short one.
+///
+/// The buffer is generic over the sample type `T` (e.g. `f32`, `f64`, or a SIMD vector holding
+/// multiple lanes) and over the storage `S`. The default heap allocated `Vec` can be resized for
+/// the current sample rate, while [`FixedCircleBuffer`] uses an inline array for short fixed
+/// length lines like allpass diffusers. When the capacity is a power of two, wrapping around is
+/// done with a bit mask instead of a modulo.
+pub struct CircleBuffer<T = f32, S = Vec<T>> {
     num_samples: usize,
-    buffer: Vec<f32>,
+    buffer: S,
+    /// The position the next sample will be written to. This is also the position of the oldest
+    /// sample in the buffer.
     write_pos: usize,
+    /// `num_samples - 1` if `num_samples` is a power of two. Used as a fast path for wrapping
+    /// positions around.
+    wrap_mask: Option<usize>,
+    _sample: PhantomData<T>,
 }
 
-impl CircleBuffer {
+/// A [`CircleBuffer`] with a capacity of `N` samples stored inline.
+pub type FixedCircleBuffer<T, const N: usize> = CircleBuffer<T, [T; N]>;
+
+impl<T: Copy + Default> Default for CircleBuffer<T, Vec<T>> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy + Default> CircleBuffer<T, Vec<T>> {
+    /// Create an empty buffer. It needs to be resized before it can be written to.
     pub fn new() -> Self {
-        let buffer = Vec::new();
-        let sample_rate = 1.0;
         CircleBuffer {
-            sample_rate,
             num_samples: 0,
-            buffer,
+            buffer: Vec::new(),
             write_pos: 0,
+            wrap_mask: None,
+            _sample: PhantomData,
         }
     }
 
-    pub fn resize(&mut self, sample_rate: f32, capacity_factor: usize) {
-        nih_debug_assert!(sample_rate > 0.0);
+    /// Resize the buffer to hold `num_samples` samples. The old contents are discarded since they
+    /// would otherwise end up at the wrong offsets, and they may have been recorded at a different
+    /// sample rate.
+    pub fn resize(&mut self, num_samples: usize) {
+        self.num_samples = num_samples;
+        self.wrap_mask = wrap_mask(num_samples);
+        self.write_pos = 0;
+
+        self.buffer.clear();
+        self.buffer.resize(self.num_samples, T::default());
+    }
+
+    /// The same as [`resize()`][Self::resize()], but the capacity gets rounded up to the next power
+    /// of two so wrapping can always use the bit mask fast path. This means that
+    /// [`capacity()`][Self::capacity()] can be up to twice as large as requested.
+    pub fn resize_power_of_two(&mut self, num_samples: usize) {
+        self.resize(num_samples.next_power_of_two());
+    }
 
-        self.sample_rate = sample_rate;
-        self.num_samples = ((sample_rate * capacity_factor as f32).ceil()) as usize;
+    /// Release the buffer's memory. It needs to be resized again before it can be written to.
+    pub fn free(&mut self) {
+        self.num_samples = 0;
+        self.wrap_mask = None;
+        self.write_pos = 0;
+        self.buffer = Vec::new();
+    }
+}
 
-        self.buffer.resize(self.num_samples, 0.0);
+impl<T: Copy + Default, const N: usize> CircleBuffer<T, [T; N]> {
+    /// Create a buffer holding `N` samples of silence.
+    pub fn new_fixed() -> Self {
+        CircleBuffer {
+            num_samples: N,
+            buffer: [T::default(); N],
+            write_pos: 0,
+            wrap_mask: wrap_mask(N),
+            _sample: PhantomData,
+        }
     }
+}
 
-    pub fn samples(&self) -> usize {
+impl<T, S> CircleBuffer<T, S>
+where
+    T: Copy + Default,
+    S: AsRef<[T]> + AsMut<[T]>,
+{
+    /// The number of samples the buffer can hold. This is one more than the largest offset that
+    /// can be read without clamping.
+    pub fn capacity(&self) -> usize {
         self.num_samples
     }
 
-    pub fn write(&mut self, sample: f32) {
-        self.buffer[self.write_pos] = sample;
-        self.write_pos += 1 as usize;
-        self.write_pos %= self.num_samples;
+    /// Fill the buffer with silence without changing its capacity. This does not allocate.
+    pub fn clear(&mut self) {
+        self.buffer.as_mut().fill(T::default());
+        self.write_pos = 0;
+    }
+
+    /// Write a single sample, overwriting the oldest sample in the buffer.
+    pub fn write(&mut self, sample: T) {
+        nih_debug_assert_ne!(self.num_samples, 0);
+
+        self.buffer.as_mut()[self.write_pos] = sample;
+        self.write_pos += 1;
+        if self.write_pos == self.num_samples {
+            self.write_pos = 0;
+        }
+    }
+
+    /// Write a slice of samples in chronological order. After this call `read(0)` returns the last
+    /// sample from `samples`. If `samples` is longer than the buffer, only its tail is kept.
+    pub fn write_slice(&mut self, samples: &[T]) {
+        nih_debug_assert_ne!(self.num_samples, 0);
+
+        // Anything that would be overwritten again within this same call can be skipped
+        let samples = &samples[samples.len().saturating_sub(self.num_samples)..];
+
+        let first_len = samples.len().min(self.num_samples - self.write_pos);
+        let (first, second) = samples.split_at(first_len);
+        let buffer = self.buffer.as_mut();
+        buffer[self.write_pos..self.write_pos + first_len].copy_from_slice(first);
+        buffer[..second.len()].copy_from_slice(second);
+
+        self.write_pos = self.wrap(self.write_pos + samples.len());
+    }
+
+    /// Read the sample that was written `samples_ago` samples ago, where `0` is the most recently
+    /// written sample. Offsets of `num_samples` or more are clamped to the oldest sample.
+    pub fn read(&self, samples_ago: usize) -> T {
+        if self.num_samples == 0 {
+            return T::default();
+        }
+
+        self.buffer.as_ref()[self.read_pos(samples_ago.min(self.num_samples - 1))]
+    }
+
+    /// Fill `output` with consecutive samples in chronological order, so the last sample in
+    /// `output` is the one from `samples_ago` samples ago and the first sample is from
+    /// `samples_ago + output.len() - 1` samples ago. Offsets past the oldest sample are clamped the
+    /// same way [`read()`][Self::read()] clamps them.
+    pub fn read_into(&self, samples_ago: usize, output: &mut [T]) {
+        if self.num_samples == 0 {
+            output.fill(T::default());
+            return;
+        }
+
+        let buffer = self.buffer.as_ref();
+
+        // The start of `output` may reach further back than the buffer does, those samples all
+        // get clamped to the oldest sample
+        let num_clamped = (samples_ago + output.len())
+            .saturating_sub(self.num_samples)
+            .min(output.len());
+        let (clamped, available) = output.split_at_mut(num_clamped);
+        clamped.fill(buffer[self.write_pos]);
+        if available.is_empty() {
+            return;
+        }
+
+        let start_pos = self.read_pos(samples_ago + available.len() - 1);
+        let first_len = available.len().min(self.num_samples - start_pos);
+        let (first, second) = available.split_at_mut(first_len);
+        first.copy_from_slice(&buffer[start_pos..start_pos + first_len]);
+        second.copy_from_slice(&buffer[..second.len()]);
+    }
+
+    /// The `len` consecutive samples ending with the sample that was written `samples_ago` samples
+    /// ago, in chronological order. These are borrowed directly from the buffer, so they must not
+    /// wrap around its end. That's always the case when the capacity is a multiple of `len` and
+    /// samples are only ever written in groups of `len`, like the frames in a
+    /// [`DelayLine`][crate::delay_line::DelayLine].
+    pub fn read_slice(&self, samples_ago: usize, len: usize) -> &[T] {
+        if len == 0 {
+            return &[];
+        }
+        nih_debug_assert!(samples_ago + len <= self.num_samples);
+
+        let start_pos = self.read_pos(samples_ago + len - 1);
+        nih_debug_assert!(start_pos + len <= self.num_samples);
+
+        &self.buffer.as_ref()[start_pos..start_pos + len]
+    }
+
+    /// The index in `buffer` for a sample that was written `samples_ago` samples ago. This must be
+    /// smaller than `num_samples`.
+    fn read_pos(&self, samples_ago: usize) -> usize {
+        nih_debug_assert!(samples_ago < self.num_samples);
+
+        self.wrap(self.write_pos + (self.num_samples - 1) - samples_ago)
+    }
+
+    /// Wrap a position that's smaller than `2 * num_samples` back into the buffer.
+    #[inline]
+    fn wrap(&self, pos: usize) -> usize {
+        match self.wrap_mask {
+            Some(mask) => pos & mask,
+            None => pos % self.num_samples,
+        }
+    }
+}
+
+fn wrap_mask(num_samples: usize) -> Option<usize> {
+    if num_samples.is_power_of_two() {
+        Some(num_samples - 1)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    fn buffer(num_samples: usize) -> CircleBuffer<f32> {
+        let mut buffer = CircleBuffer::new();
+        buffer.resize(num_samples);
+        buffer
+    }
+
+    /// Write `0.0, 1.0, 2.0, ...` so every sample's value is the number of samples written before
+    /// it.
+    fn write_ramp<S: AsRef<[f32]> + AsMut<[f32]>>(buffer: &mut CircleBuffer<f32, S>, len: usize) {
+        for i in 0..len {
+            buffer.write(i as f32);
+        }
+    }
+
+    /// A small deterministic xorshift generator, so the randomized tests are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// A number in `0..=max`.
+        fn up_to(&mut self, max: usize) -> usize {
+            (self.next() % (max as u64 + 1)) as usize
+        }
+    }
+
+    #[test]
+    fn read_across_wrap() {
+        let mut buffer = buffer(5);
+        write_ramp(&mut buffer, 12);
+
+        for samples_ago in 0..5 {
+            assert_eq!(buffer.read(samples_ago), (11 - samples_ago) as f32);
+        }
     }
 
-    pub fn read(&self, read_offset: usize) -> f32 {
-        let read_pos = self._get_read_pos(read_offset);
-        return self.buffer[read_pos];
+    #[test]
+    fn read_into_across_wrap() {
+        let mut buffer = buffer(5);
+        // The write position ends up at index 2, so the oldest samples are at the end of the
+        // buffer and the newest samples are at the start
+        write_ramp(&mut buffer, 12);
+
+        let mut output = [0.0; 4];
+        buffer.read_into(0, &mut output);
+        assert_eq!(output, [8.0, 9.0, 10.0, 11.0]);
+
+        let mut output = [0.0; 3];
+        buffer.read_into(1, &mut output);
+        assert_eq!(output, [8.0, 9.0, 10.0]);
     }
 
-    fn _get_read_pos(&self, read_offset: usize) -> usize {
-        let read_offset = if read_offset >= self.num_samples {
-            dbg!("read position would loop the buffer, so is being capped to its max value, read_offset: {}, buffer_length: {}", read_offset, self.num_samples);
-            0
-        } else if read_offset < 0 as usize {
-            dbg!("read position would loop the buffer, so is being capped to its min value, read_offset: {}, buffer_length: {}", read_offset, self.num_samples);
-            self.num_samples
-        } else {
-            self.num_samples - read_offset
+    #[test]
+    fn write_slice_across_wrap() {
+        let mut buffer = buffer(5);
+        write_ramp(&mut buffer, 3);
+        buffer.write_slice(&[3.0, 4.0, 5.0, 6.0]);
+
+        let mut output = [0.0; 5];
+        buffer.read_into(0, &mut output);
+        assert_eq!(output, [2.0, 3.0, 4.0, 5.0, 6.0]);
+
+        // Only the tail of a slice that's longer than the buffer is kept
+        buffer.write_slice(&[10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0]);
+        buffer.read_into(0, &mut output);
+        assert_eq!(output, [12.0, 13.0, 14.0, 15.0, 16.0]);
+        assert_eq!(buffer.read(0), 16.0);
+    }
+
+    #[test]
+    fn read_slice_across_wrap() {
+        let mut buffer = buffer(6);
+        // Seven pairs, so the newest pair is at the start of the buffer and the oldest pair is at
+        // its end
+        write_ramp(&mut buffer, 14);
+
+        assert_eq!(buffer.read_slice(0, 2), [12.0, 13.0]);
+        assert_eq!(buffer.read_slice(2, 2), [10.0, 11.0]);
+        assert_eq!(buffer.read_slice(4, 2), [8.0, 9.0]);
+    }
+
+    #[test]
+    fn offsets_past_the_oldest_sample_are_clamped() {
+        let mut buffer = buffer(5);
+        write_ramp(&mut buffer, 12);
+
+        let oldest = buffer.read(4);
+        assert_eq!(oldest, 7.0);
+        assert_eq!(buffer.read(5), oldest);
+        assert_eq!(buffer.read(6), oldest);
+        assert_eq!(buffer.read(usize::MAX / 2), oldest);
+
+        let mut output = [0.0; 4];
+        buffer.read_into(3, &mut output);
+        assert_eq!(output, [7.0, 7.0, 7.0, 8.0]);
+        buffer.read_into(5, &mut output);
+        assert_eq!(output, [7.0; 4]);
+    }
+
+    #[test]
+    fn unsized_and_freed_buffers_read_silence() {
+        let buffer = CircleBuffer::<f32>::new();
+        assert_eq!(buffer.read(0), 0.0);
+        let mut output = [1.0; 3];
+        buffer.read_into(0, &mut output);
+        assert_eq!(output, [0.0; 3]);
+
+        let mut buffer = self::buffer(4);
+        write_ramp(&mut buffer, 4);
+        buffer.free();
+        assert_eq!(buffer.num_samples, 0);
+        assert_eq!(buffer.read(0), 0.0);
+        let mut output = [1.0; 3];
+        buffer.read_into(0, &mut output);
+        assert_eq!(output, [0.0; 3]);
+    }
+
+    #[test]
+    fn clear_keeps_the_capacity() {
+        let mut buffer = buffer(5);
+        write_ramp(&mut buffer, 7);
+        buffer.clear();
+
+        assert_eq!(buffer.num_samples, 5);
+        for samples_ago in 0..5 {
+            assert_eq!(buffer.read(samples_ago), 0.0);
+        }
+    }
+
+    #[test]
+    fn power_of_two_capacity_uses_the_mask() {
+        assert_eq!(buffer(8).wrap_mask, Some(7));
+        assert_eq!(buffer(1).wrap_mask, Some(0));
+        assert_eq!(buffer(5).wrap_mask, None);
+
+        let mut buffer = CircleBuffer::<f32>::new();
+        buffer.resize_power_of_two(5);
+        assert_eq!(buffer.capacity(), 8);
+        assert_eq!(buffer.wrap_mask, Some(7));
+        buffer.resize_power_of_two(8);
+        assert_eq!(buffer.capacity(), 8);
+
+        assert_eq!(
+            FixedCircleBuffer::<f32, 16>::new_fixed().wrap_mask,
+            Some(15)
+        );
+        assert_eq!(FixedCircleBuffer::<f32, 6>::new_fixed().wrap_mask, None);
+    }
+
+    #[test]
+    fn mask_matches_modulo() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for num_samples in [1, 2, 8, 64] {
+            let mut masked = buffer(num_samples);
+            let mut modulo = buffer(num_samples);
+            assert!(masked.wrap_mask.is_some());
+            modulo.wrap_mask = None;
+
+            for i in 0..1000 {
+                let sample = i as f32;
+                if rng.up_to(3) == 0 {
+                    let len = rng.up_to(num_samples * 2);
+                    let samples: Vec<f32> = (0..len).map(|j| sample + j as f32 / 100.0).collect();
+                    masked.write_slice(&samples);
+                    modulo.write_slice(&samples);
+                } else {
+                    masked.write(sample);
+                    modulo.write(sample);
+                }
+                assert_eq!(masked.write_pos, modulo.write_pos);
+
+                let samples_ago = rng.up_to(num_samples + 2);
+                assert_eq!(masked.read(samples_ago), modulo.read(samples_ago));
+
+                let mut masked_output = vec![0.0; rng.up_to(num_samples + 2)];
+                let mut modulo_output = masked_output.clone();
+                masked.read_into(samples_ago, &mut masked_output);
+                modulo.read_into(samples_ago, &mut modulo_output);
+                assert_eq!(masked_output, modulo_output);
+            }
+        }
+    }
+
+    /// Compare a buffer against a `VecDeque` that holds the last `num_samples` samples, starting
+    /// out filled with silence just like the buffer.
+    fn check_against_model<S: AsRef<[f32]> + AsMut<[f32]>>(
+        buffer: &mut CircleBuffer<f32, S>,
+        rng: &mut Rng,
+    ) {
+        let num_samples = buffer.capacity();
+        let mut model = VecDeque::from(vec![0.0; num_samples]);
+        let model_read = |model: &VecDeque<f32>, samples_ago: usize| {
+            model[num_samples - 1 - samples_ago.min(num_samples - 1)]
         };
 
-        (self.write_pos + (self.num_samples - 1) + read_offset) % self.num_samples
+        let mut next_sample = 1.0;
+        for _ in 0..500 {
+            match rng.up_to(3) {
+                0 => {
+                    buffer.write(next_sample);
+                    model.pop_front();
+                    model.push_back(next_sample);
+                    next_sample += 1.0;
+                }
+                1 => {
+                    let len = rng.up_to(num_samples * 2 + 1);
+                    let samples: Vec<f32> = (0..len).map(|i| next_sample + i as f32).collect();
+                    next_sample += len as f32;
+
+                    buffer.write_slice(&samples);
+                    for sample in samples {
+                        model.pop_front();
+                        model.push_back(sample);
+                    }
+                }
+                2 => {
+                    let samples_ago = rng.up_to(num_samples * 2);
+                    assert_eq!(buffer.read(samples_ago), model_read(&model, samples_ago));
+                }
+                _ => {
+                    let samples_ago = rng.up_to(num_samples * 2);
+                    let mut output = vec![0.0; rng.up_to(num_samples * 2)];
+                    buffer.read_into(samples_ago, &mut output);
+
+                    let expected: Vec<f32> = (0..output.len())
+                        .map(|i| model_read(&model, samples_ago + output.len() - 1 - i))
+                        .collect();
+                    assert_eq!(output, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn matches_vec_deque_model() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..200 {
+            let num_samples = rng.up_to(40).max(1);
+            check_against_model(&mut buffer(num_samples), &mut rng);
+        }
+    }
+
+    #[test]
+    fn fixed_buffer_matches_vec_deque_model() {
+        let mut rng = Rng(0xdead_beef_cafe_f00d);
+        check_against_model(&mut FixedCircleBuffer::<f32, 6>::new_fixed(), &mut rng);
+        check_against_model(&mut FixedCircleBuffer::<f32, 8>::new_fixed(), &mut rng);
+    }
+
+    #[test]
+    fn f64_samples() {
+        let mut buffer = FixedCircleBuffer::<f64, 4>::new_fixed();
+        buffer.write_slice(&[0.25, 0.5, 0.75, 1.0, 1.25]);
+
+        let mut output = [0.0f64; 4];
+        buffer.read_into(0, &mut output);
+        assert_eq!(output, [0.5, 0.75, 1.0, 1.25]);
     }
 }
@@ -0,0 +1,219 @@
+use nih_plug::prelude::*;
+
+use crate::circular_buffer::CircleBuffer;
+
+/// A multichannel delay line that stores its frames contiguously, so all channels of a single
+/// frame sit next to each other in memory.
+///
+/// Reads use the same samples ago semantics as [`CircleBuffer`]: an offset of `0` is the most
+/// recently written frame, and offsets past the oldest frame are clamped to that oldest frame.
+/// Both individual channels and whole frames can be read, which allows cross-channel processing
+/// without having to borrow separate per-channel buffers.
+pub struct DelayLine<T = f32> {
+    num_channels: usize,
+    /// The number of frames that can be read without clamping.
+    num_frames: usize,
+    /// `num_channels` interleaved samples per frame. Frames are always written as a whole, so a
+    /// frame never wraps around the end of the buffer. The buffer is rounded up to a power of two
+    /// frames, which lets it wrap around using a bit mask whenever the channel count is also a
+    /// power of two.
+    buffer: CircleBuffer<T>,
+}
+
+impl<T: Copy + Default> Default for DelayLine<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy + Default> DelayLine<T> {
+    /// Create an empty delay line. It needs to be resized before it can be written to.
+    pub fn new() -> Self {
+        DelayLine {
+            num_channels: 0,
+            num_frames: 0,
+            buffer: CircleBuffer::new(),
+        }
+    }
+
+    /// Resize the delay line to hold `capacity_factor` seconds of audio for `num_channels`
+    /// channels. Since changing the channel count changes the layout of every frame, this clears
+    /// the delay line.
+    pub fn resize(&mut self, num_channels: usize, sample_rate: f32, capacity_factor: usize) {
+        nih_debug_assert!(sample_rate > 0.0);
+
+        self.num_channels = num_channels;
+        self.num_frames = ((sample_rate * capacity_factor as f32).ceil()) as usize;
+        self.buffer
+            .resize(self.num_frames.next_power_of_two() * self.num_channels);
+    }
+
+    pub fn channels(&self) -> usize {
+        self.num_channels
+    }
+
+    /// The number of frames the delay line can hold. This is one more than the largest offset that
+    /// can be read without clamping.
+    pub fn frames(&self) -> usize {
+        self.num_frames
+    }
+
+    /// Fill the delay line with silence without changing its size. This does not allocate.
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Release the delay line's memory. It needs to be resized again before it can be written to.
+    pub fn free(&mut self) {
+        self.num_frames = 0;
+        self.buffer.free();
+    }
+
+    /// Write a frame, overwriting the oldest frame in the delay line. `frame` should yield one
+    /// sample per channel. Missing channels are written as silence and any extra samples are
+    /// ignored.
+    pub fn write_frame(&mut self, frame: impl IntoIterator<Item = T>) {
+        nih_debug_assert_ne!(self.num_frames, 0);
+
+        let mut frame = frame.into_iter();
+        for _ in 0..self.num_channels {
+            self.buffer.write(frame.next().unwrap_or_default());
+        }
+    }
+
+    /// Read a single channel from the frame that was written `samples_ago` frames ago.
+    pub fn read(&self, channel: usize, samples_ago: usize) -> T {
+        nih_debug_assert!(channel < self.num_channels);
+        if self.num_frames == 0 || channel >= self.num_channels {
+            return T::default();
+        }
+
+        // The frame's last channel is the sample that was written last
+        let frame_end = samples_ago.min(self.num_frames - 1) * self.num_channels;
+        self.buffer
+            .read(frame_end + (self.num_channels - 1 - channel))
+    }
+
+    /// Read all channels of the frame that was written `samples_ago` frames ago. Offsets of
+    /// `frames()` or more are clamped to the oldest frame. This is empty if the delay line has not
+    /// been sized yet.
+    pub fn read_frame(&self, samples_ago: usize) -> &[T] {
+        if self.num_frames == 0 {
+            return &[];
+        }
+
+        self.buffer.read_slice(
+            samples_ago.min(self.num_frames - 1) * self.num_channels,
+            self.num_channels,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A delay line holding `num_frames` frames, where every sample in frame `i` is
+    /// `i * 10 + channel`.
+    fn delay_line(num_channels: usize, num_frames: usize, frames_written: usize) -> DelayLine {
+        let mut delay_line = DelayLine::new();
+        delay_line.resize(num_channels, num_frames as f32, 1);
+        for i in 0..frames_written {
+            delay_line.write_frame((0..num_channels).map(|channel| (i * 10 + channel) as f32));
+        }
+
+        delay_line
+    }
+
+    #[test]
+    fn read_frames_across_wrap() {
+        for num_channels in [1, 2, 3] {
+            let delay_line = delay_line(num_channels, 5, 23);
+
+            for samples_ago in 0..5 {
+                let frame_value = ((22 - samples_ago) * 10) as f32;
+                let expected: Vec<f32> = (0..num_channels)
+                    .map(|channel| frame_value + channel as f32)
+                    .collect();
+                assert_eq!(delay_line.read_frame(samples_ago), expected);
+                for (channel, sample) in expected.iter().enumerate() {
+                    assert_eq!(delay_line.read(channel, samples_ago), *sample);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn offsets_past_the_oldest_frame_are_clamped() {
+        // The buffer is rounded up to eight frames, but only five of them can be read
+        let delay_line = delay_line(2, 5, 23);
+        let oldest = delay_line.read_frame(4).to_vec();
+        assert_eq!(oldest, [180.0, 181.0]);
+
+        for samples_ago in [5, 6, 8, 100] {
+            assert_eq!(delay_line.read_frame(samples_ago), oldest);
+            assert_eq!(delay_line.read(0, samples_ago), oldest[0]);
+            assert_eq!(delay_line.read(1, samples_ago), oldest[1]);
+        }
+    }
+
+    #[test]
+    fn missing_channels_are_written_as_silence() {
+        let mut delay_line = delay_line(3, 4, 0);
+        delay_line.write_frame([1.0]);
+        delay_line.write_frame([2.0, 3.0, 4.0, 5.0]);
+
+        assert_eq!(delay_line.read_frame(1), [1.0, 0.0, 0.0]);
+        assert_eq!(delay_line.read_frame(0), [2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn resizing_discards_the_old_contents() {
+        let mut delay_line = DelayLine::new();
+        delay_line.resize(2, 44_100.0, 2);
+        assert_eq!(delay_line.frames(), 88_200);
+        assert_eq!(delay_line.channels(), 2);
+        for i in 0..10_000 {
+            delay_line.write_frame([i as f32 + 1.0, -(i as f32) - 1.0]);
+        }
+
+        delay_line.resize(4, 96_000.0, 2);
+        assert_eq!(delay_line.frames(), 192_000);
+        assert_eq!(delay_line.channels(), 4);
+        for samples_ago in 0..delay_line.frames() {
+            assert_eq!(delay_line.read_frame(samples_ago), [0.0; 4]);
+        }
+    }
+
+    #[test]
+    fn clear_keeps_the_size() {
+        let mut delay_line = delay_line(2, 5, 7);
+        delay_line.clear();
+
+        assert_eq!(delay_line.frames(), 5);
+        assert_eq!(delay_line.channels(), 2);
+        for samples_ago in 0..5 {
+            assert_eq!(delay_line.read_frame(samples_ago), [0.0; 2]);
+        }
+
+        // Writing still works as before after clearing
+        delay_line.write_frame([1.0, 2.0]);
+        assert_eq!(delay_line.read_frame(0), [1.0, 2.0]);
+        assert_eq!(delay_line.read_frame(1), [0.0; 2]);
+    }
+
+    #[test]
+    fn freed_delay_line_reads_silence() {
+        let mut delay_line = delay_line(2, 5, 7);
+        delay_line.free();
+
+        assert_eq!(delay_line.frames(), 0);
+        assert!(delay_line.read_frame(0).is_empty());
+    }
+
+    #[test]
+    fn unsized_delay_line_reads_silence() {
+        let delay_line = DelayLine::<f32>::new();
+        assert!(delay_line.read_frame(0).is_empty());
+    }
+}
@@ -1,72 +1,207 @@
+use atomic_float::AtomicF32;
 use nih_plug::prelude::Editor;
 use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
 use nih_plug_vizia::widgets::*;
 use nih_plug_vizia::{assets, create_vizia_editor, ViziaState, ViziaTheming};
 
-use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
 
-use crate::BorderSecurityPluginParams;
+use crate::meters::BucketMeter;
+use crate::spectrum::SpectrumOutput;
+use crate::waveform::WaveformOutput;
+use crate::{BorderSecurityPluginParams, BUCKETS};
 
-use widgets::time_slider::*;
+use widgets::level_meter::{LevelMeter, LevelMeterData};
+use widgets::param_knob::*;
+use widgets::range_slider::RangeSlider;
 
-#[derive(Lens)]
-struct Data {
-    params: Arc<BorderSecurityPluginParams>,
+use self::gate_meter::GateMeter;
+use self::preset_browser::PresetBrowser;
+use self::spectrum::SpectrumAnalyzer;
+use self::tap_timeline::TapTimeline;
+use self::transfer_curve::TransferCurve;
+use self::undo_history::{UndoEvent, UndoHistory};
+use self::waveform::Waveform;
+
+mod gate_meter;
+mod preset_browser;
+mod spectrum;
+mod tap_timeline;
+mod transfer_curve;
+mod undo_history;
+mod waveform;
+
+/// Everything the editor shares with the plugin. A copy of this is used as the editor's root model
+/// every time the editor is opened.
+#[derive(Lens, Clone)]
+pub(crate) struct Data {
+    pub params: Arc<BorderSecurityPluginParams>,
+    pub waveform: Arc<Mutex<WaveformOutput>>,
+    pub bucket_meters: Arc<[BucketMeter; BUCKETS]>,
+    pub input_level: Arc<LevelMeterData>,
+    pub output_level: Arc<LevelMeterData>,
+    pub spectrum: Arc<Mutex<SpectrumOutput>>,
+    /// The host's tempo, for `TimeFormat::Beats` and `SnapGrid::beats()`.
+    pub tempo: Arc<AtomicF32>,
 }
 
 impl Model for Data {}
 
+/// The colors used to tell the buckets apart in the waveform and the timeline, cycled through if
+/// there are more buckets than colors.
+const TAP_COLORS: [(u8, u8, u8); 4] = [
+    (212, 214, 77),
+    (86, 180, 233),
+    (230, 120, 90),
+    (160, 120, 220),
+];
+
 // Makes sense to also define this here, makes it a bit easier to keep track of
 pub(crate) fn default_state() -> Arc<ViziaState> {
-    ViziaState::new(|| (600, 400))
+    ViziaState::new(|| (800, 700))
 }
 
-pub(crate) fn create(
-    params: Arc<BorderSecurityPluginParams>,
-    editor_state: Arc<ViziaState>,
-) -> Option<Box<dyn Editor>> {
-    create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, _| {
+pub(crate) fn create(data: Data, editor_state: Arc<ViziaState>) -> Option<Box<dyn Editor>> {
+    create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, context| {
         assets::register_noto_sans_light(cx);
         assets::register_noto_sans_thin(cx);
 
-        Data {
-            params: params.clone(),
-        }
-        .build(cx);
+        data.clone().build(cx);
+        UndoHistory::new(context.clone()).build(cx);
 
         ResizeHandle::new(cx);
 
         VStack::new(cx, |cx| {
-            Label::new(cx, "Border Security GUI")
-                .font_family(vec![FamilyOwned::Name(String::from(
-                    assets::NOTO_SANS_THIN,
-                ))])
-                .font_size(30.0)
-                .height(Pixels(50.0))
-                .child_top(Stretch(1.0))
-                .child_bottom(Pixels(0.0));
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Border Security GUI")
+                    .font_family(vec![FamilyOwned::Name(String::from(
+                        assets::NOTO_SANS_THIN,
+                    ))])
+                    .font_size(30.0)
+                    .width(Stretch(1.0))
+                    .height(Pixels(50.0))
+                    .child_top(Stretch(1.0))
+                    .child_bottom(Pixels(0.0));
+                Button::new(
+                    cx,
+                    |cx| cx.emit(UndoEvent::Undo),
+                    |cx| Label::new(cx, "Undo"),
+                )
+                .disabled(UndoHistory::can_undo.map(|can_undo| !can_undo))
+                .top(Stretch(1.0));
+                Button::new(
+                    cx,
+                    |cx| cx.emit(UndoEvent::Redo),
+                    |cx| Label::new(cx, "Redo"),
+                )
+                .disabled(UndoHistory::can_redo.map(|can_redo| !can_redo))
+                .top(Stretch(1.0));
+            })
+            .col_between(Pixels(4.0))
+            .height(Pixels(50.0));
+            PresetBrowser::new(cx, context.clone())
+                .width(Stretch(1.0))
+                .height(Pixels(90.0));
+            HStack::new(cx, |cx| {
+                Label::new(cx, "In").width(Pixels(30.0));
+                LevelMeter::new(cx, Data::input_level)
+                    .background_color(Color::rgb(28, 32, 46))
+                    .color(Color::rgb(212, 214, 77))
+                    .width(Stretch(1.0))
+                    .height(Pixels(12.0));
+                Label::new(cx, "Out").width(Pixels(30.0));
+                LevelMeter::new(cx, Data::output_level)
+                    .background_color(Color::rgb(28, 32, 46))
+                    .color(Color::rgb(212, 214, 77))
+                    .width(Stretch(1.0))
+                    .height(Pixels(12.0));
+            })
+            .col_between(Pixels(10.0))
+            .child_top(Stretch(1.0))
+            .child_bottom(Stretch(1.0))
+            .height(Pixels(20.0));
+            HStack::new(cx, |cx| {
+                Waveform::new(cx, Data::waveform)
+                    .background_color(Color::rgb(28, 32, 46))
+                    .color(Color::rgb(200, 200, 200))
+                    .border_color(Color::rgb(70, 74, 90))
+                    .width(Stretch(1.0))
+                    .height(Stretch(1.0));
+                SpectrumAnalyzer::new(cx, Data::spectrum)
+                    .background_color(Color::rgb(28, 32, 46))
+                    .width(Stretch(1.0))
+                    .height(Stretch(1.0));
+            })
+            .col_between(Pixels(10.0))
+            .height(Pixels(100.0));
             Label::new(cx, "Departure Time");
-            for i in 0..params.delay_params.len() {
+            TapTimeline::new(cx, Data::params, Data::bucket_meters)
+                .background_color(Color::rgb(28, 32, 46))
+                .border_color(Color::rgb(70, 74, 90))
+                .width(Stretch(1.0))
+                .height(Pixels(90.0));
+            for i in 0..BUCKETS {
                 HStack::new(cx, |cx| {
-                    ParamSlider::new(cx, Data::params, move |params| {
-                        &params.delay_params[i].threshold
-                    });
-                    TimeSlider::new(cx, Data::params, move |params| {
-                        &params.delay_params[i].delay
-                    })
-                    .set_style(TimeSliderStyle::CurrentStep { even: true })
-                    .background_color(Color::rgb(120, 86, 28))
+                    RangeSlider::new(
+                        cx,
+                        Data::params,
+                        move |params| &params.delay_params[i].threshold,
+                        move |params| &params.delay_params[i].capacity,
+                    )
+                    .background_color(Color::rgb(28, 32, 46))
                     .color(Color::rgb(212, 214, 77))
-                    .border_color(Color::rgb(28, 32, 46));
-                    ParamSlider::new(cx, Data::params, move |params| {
-                        &params.delay_params[i].capacity
-                    });
-                    ParamSlider::new(cx, Data::params, move |params| {
+                    .width(Pixels(240.0))
+                    .height(Pixels(30.0));
+                    ParamKnob::new(cx, Data::params, move |params| {
                         &params.delay_params[i].factor
-                    });
+                    })
+                    .width(Pixels(60.0))
+                    .height(Pixels(80.0));
+                    GateMeter::new(
+                        cx,
+                        Data::bucket_meters.map(move |bucket_meters| {
+                            bucket_meters[i].level.load(Ordering::Relaxed)
+                        }),
+                        Data::params.map(move |params| params.delay_params[i].threshold.value()),
+                        Data::params.map(move |params| params.delay_params[i].capacity.value()),
+                        Data::bucket_meters.map(move |bucket_meters| {
+                            bucket_meters[i].activity.load(Ordering::Relaxed)
+                        }),
+                    )
+                    .background_color(Color::rgb(28, 32, 46))
+                    .color(Color::rgb(212, 214, 77))
+                    .width(Pixels(140.0))
+                    .height(Pixels(16.0))
+                    .top(Stretch(1.0))
+                    .bottom(Stretch(1.0));
+                    TransferCurve::new(
+                        cx,
+                        Data::bucket_meters.map(move |bucket_meters| {
+                            bucket_meters[i].level.load(Ordering::Relaxed)
+                        }),
+                        Data::params.map(move |params| params.delay_params[i].threshold.value()),
+                        Data::params.map(move |params| params.delay_params[i].capacity.value()),
+                        Data::params.map(move |params| params.delay_params[i].factor.value()),
+                    )
+                    .background_color(Color::rgb(28, 32, 46))
+                    .color(Color::rgb(212, 214, 77))
+                    .border_color(Color::rgb(70, 74, 90))
+                    .width(Pixels(70.0))
+                    .height(Pixels(70.0));
                 })
                 .height(Auto);
             }
         });
     })
 }
+
+/// The color for a bucket with an alpha value between `0.0` and `1.0`.
+fn tap_color(bucket: usize, alpha: f32) -> vg::Color {
+    let (r, g, b) = TAP_COLORS[bucket % TAP_COLORS.len()];
+    let mut color = vg::Color::rgb(r, g, b);
+    color.set_alphaf(alpha);
+
+    color
+}
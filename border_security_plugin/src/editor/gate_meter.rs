@@ -0,0 +1,136 @@
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use widgets::level_meter::gain_to_t;
+
+/// The lowest level shown on the meter. This matches the threshold and capacity parameters' range.
+const MIN_DB: f32 = -30.0;
+/// The highest level shown on the meter.
+const MAX_DB: f32 = 30.0;
+/// The LED's color when the gate is fully open. Its alpha follows the gate's activity.
+const LED_COLOR: (u8, u8, u8) = (120, 220, 90);
+
+/// A meter for a single bucket's gate. It shows the level of the delayed signal the gate sees on
+/// a decibel scale, the window between the threshold and the capacity, and an LED that lights up
+/// while the gate lets the signal through.
+pub struct GateMeter;
+
+impl GateMeter {
+    /// Creates a new [`GateMeter`]. `level`, `threshold`, and `capacity` are linear gains, and
+    /// `activity` is the fraction of time the gate was open between `0.0` and `1.0`.
+    pub fn new<LLevel, LThreshold, LCapacity, LActivity>(
+        cx: &mut Context,
+        level: LLevel,
+        threshold: LThreshold,
+        capacity: LCapacity,
+        activity: LActivity,
+    ) -> Handle<Self>
+    where
+        LLevel: Lens<Target = f32>,
+        LThreshold: Lens<Target = f32>,
+        LCapacity: Lens<Target = f32>,
+        LActivity: Lens<Target = f32>,
+    {
+        Self.build(cx, move |cx| {
+            HStack::new(cx, move |cx| {
+                GateMeterBar {
+                    level,
+                    threshold,
+                    capacity,
+                }
+                .build(cx, |_| {})
+                .class("bar")
+                .width(Stretch(1.0))
+                .height(Stretch(1.0));
+
+                let (r, g, b) = LED_COLOR;
+                Element::new(cx)
+                    .class("led")
+                    .background_color(activity.map(move |activity| {
+                        // A dim LED is still visible when the gate is closed
+                        let alpha = 0.15 + 0.85 * activity.clamp(0.0, 1.0);
+                        Color::rgba(r, g, b, (alpha * 255.0) as u8)
+                    }))
+                    .border_radius(Percentage(50.0))
+                    .width(Pixels(10.0))
+                    .height(Pixels(10.0))
+                    .top(Stretch(1.0))
+                    .bottom(Stretch(1.0));
+            })
+            .col_between(Pixels(6.0));
+        })
+    }
+}
+
+impl View for GateMeter {
+    fn element(&self) -> Option<&'static str> {
+        Some("gate-meter")
+    }
+}
+
+/// The bar part of the meter. The background uses the background color, and the window and the
+/// level use the font color.
+struct GateMeterBar<LLevel, LThreshold, LCapacity>
+where
+    LLevel: Lens<Target = f32>,
+    LThreshold: Lens<Target = f32>,
+    LCapacity: Lens<Target = f32>,
+{
+    level: LLevel,
+    threshold: LThreshold,
+    capacity: LCapacity,
+}
+
+impl<LLevel, LThreshold, LCapacity> View for GateMeterBar<LLevel, LThreshold, LCapacity>
+where
+    LLevel: Lens<Target = f32>,
+    LThreshold: Lens<Target = f32>,
+    LCapacity: Lens<Target = f32>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("gate-meter-bar")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let opacity = cx.opacity();
+        let mut background_color: vg::Color = cx.background_color().into();
+        background_color.set_alphaf(background_color.a * opacity);
+        let mut level_color: vg::Color = cx.font_color().into();
+        level_color.set_alphaf(level_color.a * opacity);
+        let mut window_color = level_color;
+        window_color.set_alphaf(level_color.a * 0.35);
+
+        let mut path = vg::Path::new();
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&mut path, &vg::Paint::color(background_color));
+
+        // The plugin swaps the threshold and capacity if they cross, so the window is drawn the
+        // same way
+        let threshold_t = gain_to_t(self.threshold.get(cx), MIN_DB, MAX_DB);
+        let capacity_t = gain_to_t(self.capacity.get(cx), MIN_DB, MAX_DB);
+        let (start_t, end_t) = (threshold_t.min(capacity_t), threshold_t.max(capacity_t));
+        let mut path = vg::Path::new();
+        path.rect(
+            bounds.x + start_t * bounds.w,
+            bounds.y,
+            (end_t - start_t) * bounds.w,
+            bounds.h,
+        );
+        canvas.fill_path(&mut path, &vg::Paint::color(window_color));
+
+        // The level is drawn as a thinner bar on top of the window so both remain visible
+        let level_t = gain_to_t(self.level.get(cx), MIN_DB, MAX_DB);
+        let mut path = vg::Path::new();
+        path.rect(
+            bounds.x,
+            bounds.y + bounds.h * 0.25,
+            level_t * bounds.w,
+            bounds.h * 0.5,
+        );
+        canvas.fill_path(&mut path, &vg::Paint::color(level_color));
+    }
+}
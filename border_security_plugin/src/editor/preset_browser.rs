@@ -0,0 +1,292 @@
+use nih_plug::prelude::GuiContext;
+use nih_plug_vizia::vizia::prelude::*;
+use std::sync::Arc;
+
+use super::undo_history::UndoEvent;
+use crate::preset_file::PresetFile;
+use crate::presets::{self, PresetEntry, PresetSource};
+
+/// A panel listing the factory presets followed by the user's own presets. Clicking a preset
+/// selects it and copies its name to the text box, after which it can be loaded, renamed, or
+/// deleted. Saving stores the current parameter values as a user preset with the text box's name.
+/// Presets are loaded through the host facing state restore path, so the host sees the same
+/// parameter changes it would see when restoring a session. The current parameters can also be
+/// exported to and imported from human readable JSON or TOML files anywhere on disk.
+#[derive(Lens)]
+pub struct PresetBrowser {
+    gui_context: Arc<dyn GuiContext>,
+
+    presets: Vec<PresetEntry>,
+    /// The index in `presets` of the selected preset.
+    selected: Option<usize>,
+    /// The name used when saving or renaming a preset.
+    name_input: String,
+    /// The result of the last action, shown below the buttons.
+    status: String,
+}
+
+enum PresetBrowserEvent {
+    Select(usize),
+    SetName(String),
+    Load,
+    Save,
+    Rename,
+    Delete,
+    Import,
+    Export,
+}
+
+impl Data for PresetEntry {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl PresetBrowser {
+    /// Creates a new [`PresetBrowser`]. `gui_context` is used to read the plugin's current state
+    /// when saving presets and to restore the state when loading them.
+    pub fn new(cx: &mut Context, gui_context: Arc<dyn GuiContext>) -> Handle<Self> {
+        Self {
+            gui_context,
+
+            presets: presets::list_presets(),
+            selected: None,
+            name_input: String::new(),
+            status: String::new(),
+        }
+        .build(cx, |cx| {
+            HStack::new(cx, |cx| {
+                ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
+                    Binding::new(cx, PresetBrowser::presets, |cx, presets| {
+                        for (index, preset) in presets.get(cx).into_iter().enumerate() {
+                            let is_factory = matches!(preset.source, PresetSource::Factory(_));
+                            Label::new(cx, &preset.name)
+                                .class("preset")
+                                .toggle_class("preset--factory", is_factory)
+                                .toggle_class(
+                                    "preset--selected",
+                                    PresetBrowser::selected
+                                        .map(move |selected| *selected == Some(index)),
+                                )
+                                .background_color(PresetBrowser::selected.map(move |selected| {
+                                    if *selected == Some(index) {
+                                        Color::rgb(60, 64, 80)
+                                    } else {
+                                        Color::rgba(0, 0, 0, 0)
+                                    }
+                                }))
+                                // Factory presets are dimmed a bit to tell them apart from the
+                                // user's own presets
+                                .color(if is_factory {
+                                    Color::rgb(160, 160, 170)
+                                } else {
+                                    Color::rgb(230, 230, 230)
+                                })
+                                .child_left(Pixels(4.0))
+                                .width(Stretch(1.0))
+                                .on_press(move |cx| cx.emit(PresetBrowserEvent::Select(index)));
+                        }
+                    });
+                })
+                .width(Pixels(200.0))
+                .height(Stretch(1.0));
+
+                VStack::new(cx, |cx| {
+                    Textbox::new(cx, PresetBrowser::name_input)
+                        .on_edit(|cx, text| cx.emit(PresetBrowserEvent::SetName(text)))
+                        .width(Stretch(1.0));
+                    HStack::new(cx, |cx| {
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(PresetBrowserEvent::Load),
+                            |cx| Label::new(cx, "Load"),
+                        );
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(PresetBrowserEvent::Save),
+                            |cx| Label::new(cx, "Save"),
+                        );
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(PresetBrowserEvent::Rename),
+                            |cx| Label::new(cx, "Rename"),
+                        );
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(PresetBrowserEvent::Delete),
+                            |cx| Label::new(cx, "Delete"),
+                        );
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(PresetBrowserEvent::Import),
+                            |cx| Label::new(cx, "Import..."),
+                        );
+                        Button::new(
+                            cx,
+                            |cx| cx.emit(PresetBrowserEvent::Export),
+                            |cx| Label::new(cx, "Export..."),
+                        );
+                    })
+                    .col_between(Pixels(4.0))
+                    .height(Auto);
+                    Label::new(cx, PresetBrowser::status)
+                        .class("status")
+                        .font_size(11.0)
+                        .width(Stretch(1.0));
+                })
+                .row_between(Pixels(4.0));
+            })
+            .col_between(Pixels(10.0));
+        })
+    }
+
+    fn selected_preset(&self) -> Option<PresetEntry> {
+        self.selected
+            .and_then(|index| self.presets.get(index))
+            .cloned()
+    }
+
+    /// Reload the preset list from disk and select the preset with `source`, if it still exists.
+    fn refresh(&mut self, source: Option<&PresetSource>) {
+        self.presets = presets::list_presets();
+        self.selected = source.and_then(|source| {
+            self.presets
+                .iter()
+                .position(|preset| &preset.source == source)
+        });
+    }
+
+    fn load(&mut self, cx: &mut EventContext) {
+        let Some(preset) = self.selected_preset() else {
+            self.status = String::from("Select a preset to load");
+            return;
+        };
+
+        match presets::apply_preset(&preset, self.gui_context.get_state()) {
+            Ok(state) => {
+                self.gui_context.set_state(state);
+                cx.emit(UndoEvent::Clear);
+                self.status = format!("Loaded '{}'", preset.name);
+            }
+            Err(err) => self.status = format!("Could not load '{}': {err}", preset.name),
+        }
+    }
+
+    fn save(&mut self) {
+        let name = self.name_input.trim().to_owned();
+        match presets::save_user_preset(&name, &self.gui_context.get_state()) {
+            Ok(path) => {
+                self.refresh(Some(&PresetSource::User(path)));
+                self.status = format!("Saved '{name}'");
+            }
+            Err(err) => self.status = format!("Could not save '{name}': {err}"),
+        }
+    }
+
+    fn rename(&mut self) {
+        let Some(preset) = self.selected_preset() else {
+            self.status = String::from("Select a preset to rename");
+            return;
+        };
+        let PresetSource::User(path) = &preset.source else {
+            self.status = String::from("Factory presets can't be renamed");
+            return;
+        };
+
+        let new_name = self.name_input.trim().to_owned();
+        match presets::rename_user_preset(path, &new_name) {
+            Ok(new_path) => {
+                self.refresh(Some(&PresetSource::User(new_path)));
+                self.status = format!("Renamed '{}' to '{new_name}'", preset.name);
+            }
+            Err(err) => self.status = format!("Could not rename '{}': {err}", preset.name),
+        }
+    }
+
+    fn delete(&mut self) {
+        let Some(preset) = self.selected_preset() else {
+            self.status = String::from("Select a preset to delete");
+            return;
+        };
+        let PresetSource::User(path) = &preset.source else {
+            self.status = String::from("Factory presets can't be deleted");
+            return;
+        };
+
+        match presets::delete_user_preset(path) {
+            Ok(()) => {
+                self.refresh(None);
+                self.status = format!("Deleted '{}'", preset.name);
+            }
+            Err(err) => self.status = format!("Could not delete '{}': {err}", preset.name),
+        }
+    }
+
+    fn import(&mut self, cx: &mut EventContext) {
+        let Some(path) = preset_file_dialog().pick_file() else {
+            return;
+        };
+
+        match PresetFile::read(&path) {
+            Ok(preset_file) => {
+                let mut state = self.gui_context.get_state();
+                preset_file.apply_to_state(&mut state);
+                self.gui_context.set_state(state);
+                cx.emit(UndoEvent::Clear);
+                self.status = format!("Imported '{}'", path.display());
+            }
+            Err(err) => self.status = format!("Could not import '{}': {err}", path.display()),
+        }
+    }
+
+    fn export(&mut self) {
+        let file_name = match self.name_input.trim() {
+            "" => String::from("preset.toml"),
+            name => format!("{name}.toml"),
+        };
+        let Some(path) = preset_file_dialog().set_file_name(&file_name).save_file() else {
+            return;
+        };
+
+        match PresetFile::from_state(&self.gui_context.get_state()).write(&path) {
+            Ok(()) => self.status = format!("Exported '{}'", path.display()),
+            Err(err) => self.status = format!("Could not export '{}': {err}", path.display()),
+        }
+    }
+}
+
+/// A file dialog for human readable preset files. This blocks the GUI until it's closed.
+fn preset_file_dialog() -> rfd::FileDialog {
+    rfd::FileDialog::new()
+        .add_filter("Preset files", &["toml", "json"])
+        .add_filter("TOML", &["toml"])
+        .add_filter("JSON", &["json"])
+}
+
+impl View for PresetBrowser {
+    fn element(&self) -> Option<&'static str> {
+        Some("preset-browser")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|preset_browser_event, meta| {
+            match preset_browser_event {
+                PresetBrowserEvent::Select(index) => {
+                    self.selected = Some(*index);
+                    if let Some(preset) = self.presets.get(*index) {
+                        self.name_input = preset.name.clone();
+                    }
+                }
+                PresetBrowserEvent::SetName(name) => self.name_input = name.clone(),
+                PresetBrowserEvent::Load => self.load(cx),
+                PresetBrowserEvent::Save => self.save(),
+                PresetBrowserEvent::Rename => self.rename(),
+                PresetBrowserEvent::Delete => self.delete(),
+                PresetBrowserEvent::Import => self.import(cx),
+                PresetBrowserEvent::Export => self.export(),
+            }
+
+            meta.consume();
+        });
+    }
+}
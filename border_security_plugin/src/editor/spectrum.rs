@@ -0,0 +1,121 @@
+use nih_plug::prelude::util;
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::sync::{Arc, Mutex};
+
+use crate::spectrum::{SpectrumOutput, SpectrumSignal, SPECTRUM_WINDOW_SIZE};
+
+/// The lowest frequency shown on the analyzer.
+const MIN_FREQUENCY: f32 = 20.0;
+/// The highest frequency shown on the analyzer. This gets capped to the Nyquist frequency.
+const MAX_FREQUENCY: f32 = 20_000.0;
+/// The lowest level shown on the analyzer.
+const MIN_DB: f32 = -90.0;
+/// The highest level shown on the analyzer.
+const MAX_DB: f32 = 6.0;
+
+/// The name and color for each signal, in the same order as [`SpectrumSignal::ALL`].
+const TRACES: [(&str, (u8, u8, u8)); 3] = [
+    ("Input", (150, 150, 160)),
+    ("Wet", (86, 180, 233)),
+    ("Output", (212, 214, 77)),
+];
+
+/// An FFT spectrum analyzer showing the input, the wet signal, and the output as overlaid traces on
+/// a logarithmic frequency axis. The spectra are computed when the analyzer is drawn. The
+/// background is filled with the background color, and a legend is drawn in the top left corner.
+pub struct SpectrumAnalyzer {
+    spectrum: Arc<Mutex<SpectrumOutput>>,
+}
+
+impl SpectrumAnalyzer {
+    /// Creates a new [`SpectrumAnalyzer`] that reads the signals from `spectrum`.
+    pub fn new<LSpectrum>(cx: &mut Context, spectrum: LSpectrum) -> Handle<Self>
+    where
+        LSpectrum: Lens<Target = Arc<Mutex<SpectrumOutput>>>,
+    {
+        Self {
+            spectrum: spectrum.get(cx),
+        }
+        .build(cx, |cx| {
+            HStack::new(cx, |cx| {
+                for (name, (r, g, b)) in TRACES {
+                    Label::new(cx, name)
+                        .class("legend")
+                        .color(Color::rgb(r, g, b))
+                        .font_size(11.0)
+                        .width(Auto)
+                        .hoverable(false);
+                }
+            })
+            .col_between(Pixels(8.0))
+            .child_left(Pixels(6.0))
+            .child_top(Pixels(2.0))
+            .height(Auto)
+            .hoverable(false);
+        })
+    }
+}
+
+impl View for SpectrumAnalyzer {
+    fn element(&self) -> Option<&'static str> {
+        Some("spectrum-analyzer")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let dpi_scale = cx.scale_factor();
+        let opacity = cx.opacity();
+        let mut background_color: vg::Color = cx.background_color().into();
+        background_color.set_alphaf(background_color.a * opacity);
+
+        let mut path = vg::Path::new();
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&mut path, &vg::Paint::color(background_color));
+
+        // This only ever gets locked from the GUI thread, the audio thread writes to the other end
+        // of the ring buffer
+        let mut spectrum = self.spectrum.lock().unwrap();
+        spectrum.update();
+
+        let nyquist = spectrum.sample_rate() / 2.0;
+        let max_frequency = MAX_FREQUENCY.min(nyquist);
+        let (log_min, log_max) = (MIN_FREQUENCY.ln(), max_frequency.ln());
+        let bin_frequency = spectrum.sample_rate() / SPECTRUM_WINDOW_SIZE as f32;
+
+        for (signal, (_, (r, g, b))) in SpectrumSignal::ALL.into_iter().zip(TRACES) {
+            let mut path = vg::Path::new();
+            let mut started = false;
+            for (bin_idx, magnitude) in spectrum.spectrum(signal).iter().enumerate().skip(1) {
+                let frequency = bin_idx as f32 * bin_frequency;
+                if frequency < MIN_FREQUENCY {
+                    continue;
+                } else if frequency > max_frequency {
+                    break;
+                }
+
+                let x_t = (frequency.ln() - log_min) / (log_max - log_min);
+                let y_t =
+                    ((util::gain_to_db(*magnitude) - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0);
+                let x = bounds.x + x_t * bounds.w;
+                let y = bounds.y + (1.0 - y_t) * bounds.h;
+                if started {
+                    path.line_to(x, y);
+                } else {
+                    path.move_to(x, y);
+                    started = true;
+                }
+            }
+
+            let mut color = vg::Color::rgb(r, g, b);
+            color.set_alphaf(opacity);
+            let mut paint = vg::Paint::color(color);
+            paint.set_line_width(1.5 * dpi_scale);
+            canvas.stroke_path(&mut path, &paint);
+        }
+    }
+}
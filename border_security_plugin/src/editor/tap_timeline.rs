@@ -0,0 +1,248 @@
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use nih_plug_vizia::widgets::param_base::ParamWidgetBase;
+use nih_plug_vizia::widgets::util::{
+    remap_current_entity_x_coordinate, remap_current_entity_x_t, remap_current_entity_y_coordinate,
+    remap_current_entity_y_t,
+};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use super::tap_color;
+use crate::meters::BucketMeter;
+use crate::{BorderSecurityPluginParams, BUCKETS, MAX_DELAY};
+
+/// The radius of a bucket's node in logical pixels.
+const NODE_RADIUS: f32 = 6.0;
+/// How close to a node, in logical pixels, a click needs to be to grab that node.
+const NODE_GRAB_DISTANCE: f32 = 10.0;
+/// The spacing between the timeline's vertical grid lines in seconds.
+const GRID_SECONDS: f32 = 0.25;
+
+/// A timeline spanning the full delay range where every bucket is a node. The node's horizontal
+/// position is the bucket's delay time and its vertical position is the bucket's factor. Nodes are
+/// drawn more brightly while their bucket's gate lets the signal through. Dragging a node changes
+/// both parameters within a single drag, and double clicking a node resets them.
+///
+/// The grid uses the border color and the background is filled with the background color.
+pub struct TapTimeline {
+    taps: Vec<TapNode>,
+    bucket_meters: Arc<[BucketMeter; BUCKETS]>,
+
+    /// Set while dragging a node.
+    drag_status: Option<TapDragStatus>,
+}
+
+/// The node that's being dragged in a [`TapTimeline`].
+#[derive(Debug, Clone, Copy)]
+struct TapDragStatus {
+    /// The index of the node that's being dragged.
+    tap: usize,
+    /// The node's normalized position minus the mouse's normalized position when the drag started,
+    /// so grabbing a node slightly off center doesn't make it jump.
+    offset: (f32, f32),
+}
+
+/// The parameters a single node is bound to.
+struct TapNode {
+    delay: ParamWidgetBase,
+    factor: ParamWidgetBase,
+}
+
+impl TapTimeline {
+    /// Creates a new [`TapTimeline`] with a node for every bucket in `params`. `bucket_meters` is
+    /// used to show each bucket's gate activity.
+    pub fn new<LParams, LMeters>(
+        cx: &mut Context,
+        params: LParams,
+        bucket_meters: LMeters,
+    ) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<BorderSecurityPluginParams>> + Clone,
+        LMeters: Lens<Target = Arc<[BucketMeter; BUCKETS]>>,
+    {
+        let taps = (0..BUCKETS)
+            .map(|i| TapNode {
+                delay: ParamWidgetBase::new(cx, params.clone(), move |params| {
+                    &params.delay_params[i].delay
+                }),
+                factor: ParamWidgetBase::new(cx, params.clone(), move |params| {
+                    &params.delay_params[i].factor
+                }),
+            })
+            .collect();
+
+        Self {
+            taps,
+            bucket_meters: bucket_meters.get(cx),
+
+            drag_status: None,
+        }
+        .build(cx, |_| {})
+    }
+
+    /// The node that's closest to the mouse coordinates `(x, y)`, if it's close enough to be
+    /// grabbed.
+    fn tap_at(&self, cx: &EventContext, x: f32, y: f32) -> Option<usize> {
+        // The grab distance should be compensated for the DPI scale so it remains consistent
+        let grab_distance = NODE_GRAB_DISTANCE * cx.style.dpi_factor as f32;
+
+        self.taps
+            .iter()
+            .enumerate()
+            .map(|(i, tap)| {
+                let node_x = remap_current_entity_x_t(cx, tap.delay.unmodulated_normalized_value());
+                let node_y =
+                    remap_current_entity_y_t(cx, 1.0 - tap.factor.unmodulated_normalized_value());
+                (i, (node_x - x).hypot(node_y - y))
+            })
+            .filter(|(_, distance)| *distance <= grab_distance)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    /// The normalized delay and factor values for the mouse coordinates `(x, y)`.
+    fn remap_coordinates(cx: &EventContext, x: f32, y: f32) -> (f32, f32) {
+        (
+            remap_current_entity_x_coordinate(cx, x),
+            1.0 - remap_current_entity_y_coordinate(cx, y),
+        )
+    }
+
+    /// Move the dragged node so it follows the mouse coordinates `(x, y)`. This still needs to be
+    /// wrapped in a parameter automation gesture.
+    fn drag_to(&self, cx: &mut EventContext, drag_status: TapDragStatus, x: f32, y: f32) {
+        let (delay_t, factor_t) = Self::remap_coordinates(cx, x, y);
+        let tap = &self.taps[drag_status.tap];
+        tap.delay
+            .set_normalized_value(cx, (delay_t + drag_status.offset.0).clamp(0.0, 1.0));
+        tap.factor
+            .set_normalized_value(cx, (factor_t + drag_status.offset.1).clamp(0.0, 1.0));
+    }
+}
+
+impl View for TapTimeline {
+    fn element(&self) -> Option<&'static str> {
+        Some("tap-timeline")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match window_event {
+            // See `TimeSlider` for why triple clicks are treated as regular clicks
+            WindowEvent::MouseDown(MouseButton::Left)
+            | WindowEvent::MouseTripleClick(MouseButton::Left) => {
+                if let Some(tap) = self.tap_at(cx, cx.mouse.cursorx, cx.mouse.cursory) {
+                    cx.capture();
+                    cx.set_active(true);
+
+                    // Both parameters' gestures last for the entire drag, so hosts record the
+                    // drag as a single edit when writing automation
+                    let (delay_t, factor_t) =
+                        Self::remap_coordinates(cx, cx.mouse.cursorx, cx.mouse.cursory);
+                    let delay = &self.taps[tap].delay;
+                    let factor = &self.taps[tap].factor;
+                    delay.begin_set_parameter(cx);
+                    factor.begin_set_parameter(cx);
+                    self.drag_status = Some(TapDragStatus {
+                        tap,
+                        offset: (
+                            delay.unmodulated_normalized_value() - delay_t,
+                            factor.unmodulated_normalized_value() - factor_t,
+                        ),
+                    });
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                if let Some(tap) = self.tap_at(cx, cx.mouse.cursorx, cx.mouse.cursory) {
+                    for param_base in [&self.taps[tap].delay, &self.taps[tap].factor] {
+                        param_base.begin_set_parameter(cx);
+                        param_base.set_normalized_value(cx, param_base.default_normalized_value());
+                        param_base.end_set_parameter(cx);
+                    }
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if let Some(drag_status) = self.drag_status.take() {
+                    cx.release();
+                    cx.set_active(false);
+
+                    self.taps[drag_status.tap].delay.end_set_parameter(cx);
+                    self.taps[drag_status.tap].factor.end_set_parameter(cx);
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(x, y) => {
+                if let Some(drag_status) = self.drag_status {
+                    self.drag_to(cx, drag_status, *x, *y);
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let dpi_scale = cx.scale_factor();
+        let opacity = cx.opacity();
+        let mut background_color: vg::Color = cx.background_color().into();
+        background_color.set_alphaf(background_color.a * opacity);
+        let mut grid_color: vg::Color = cx.border_color().into();
+        grid_color.set_alphaf(grid_color.a * opacity);
+
+        let mut path = vg::Path::new();
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&mut path, &vg::Paint::color(background_color));
+
+        // Vertical lines at regular delay times, and a horizontal line at the default factor
+        let mut path = vg::Path::new();
+        let num_grid_lines = (MAX_DELAY as f32 / GRID_SECONDS).round() as usize;
+        for line in 1..num_grid_lines {
+            let x = bounds.x + (line as f32 / num_grid_lines as f32) * bounds.w;
+            path.move_to(x, bounds.y);
+            path.line_to(x, bounds.y + bounds.h);
+        }
+        if let Some(tap) = self.taps.first() {
+            let y = bounds.y + (1.0 - tap.factor.default_normalized_value()) * bounds.h;
+            path.move_to(bounds.x, y);
+            path.line_to(bounds.x + bounds.w, y);
+        }
+        let mut paint = vg::Paint::color(grid_color);
+        paint.set_line_width(dpi_scale);
+        canvas.stroke_path(&mut path, &paint);
+
+        let node_radius = NODE_RADIUS * dpi_scale;
+        for (i, tap) in self.taps.iter().enumerate() {
+            let activity = self.bucket_meters[i].activity.load(Ordering::Relaxed);
+            let x = bounds.x + tap.delay.unmodulated_normalized_value() * bounds.w;
+            let y = bounds.y + (1.0 - tap.factor.unmodulated_normalized_value()) * bounds.h;
+
+            // A line down to the time axis makes it easier to read off the delay time
+            let mut path = vg::Path::new();
+            path.move_to(x, y);
+            path.line_to(x, bounds.y + bounds.h);
+            let mut paint = vg::Paint::color(tap_color(i, 0.5 * opacity));
+            paint.set_line_width(dpi_scale);
+            canvas.stroke_path(&mut path, &paint);
+
+            let mut path = vg::Path::new();
+            path.circle(x, y, node_radius);
+            let fill_alpha = 0.3 + 0.7 * activity.clamp(0.0, 1.0);
+            canvas.fill_path(
+                &mut path,
+                &vg::Paint::color(tap_color(i, fill_alpha * opacity)),
+            );
+            let mut paint = vg::Paint::color(tap_color(i, opacity));
+            paint.set_line_width(1.5 * dpi_scale);
+            canvas.stroke_path(&mut path, &paint);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use nih_plug::prelude::util;
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+
+/// The lowest level on both axes. This matches the threshold and capacity parameters' range.
+const MIN_DB: f32 = -30.0;
+/// The highest level on both axes.
+const MAX_DB: f32 = 30.0;
+/// The radius of the dot showing the current level in logical pixels.
+const DOT_RADIUS: f32 = 3.0;
+
+/// A graph of a bucket's gate, plotting the level of the delayed signal on the X-axis against the
+/// level of the wet signal on the Y-axis, both in decibels. Only positive samples that lie between
+/// the threshold and the capacity are passed through with the bucket's factor applied. Everything
+/// else is muted, including all negative samples, so the level is the delayed signal's positive
+/// peak.
+/// The gate switches instantly at both ends of the window, so the curve has hard edges rather than
+/// a knee, and there is no hysteresis to draw.
+///
+/// The curve and the dot showing the current level use the font color, the window uses a faded
+/// version of the font color, and the unity gain diagonal uses the border color.
+pub struct TransferCurve<LLevel, LThreshold, LCapacity, LFactor>
+where
+    LLevel: Lens<Target = f32>,
+    LThreshold: Lens<Target = f32>,
+    LCapacity: Lens<Target = f32>,
+    LFactor: Lens<Target = f32>,
+{
+    level: LLevel,
+    threshold: LThreshold,
+    capacity: LCapacity,
+    factor: LFactor,
+}
+
+impl<LLevel, LThreshold, LCapacity, LFactor> TransferCurve<LLevel, LThreshold, LCapacity, LFactor>
+where
+    LLevel: Lens<Target = f32>,
+    LThreshold: Lens<Target = f32>,
+    LCapacity: Lens<Target = f32>,
+    LFactor: Lens<Target = f32>,
+{
+    /// Creates a new [`TransferCurve`]. All values are linear gains, with `level` being the current
+    /// level of the delayed signal the gate sees.
+    pub fn new(
+        cx: &mut Context,
+        level: LLevel,
+        threshold: LThreshold,
+        capacity: LCapacity,
+        factor: LFactor,
+    ) -> Handle<Self> {
+        Self {
+            level,
+            threshold,
+            capacity,
+            factor,
+        }
+        .build(cx, |_| {})
+    }
+}
+
+/// Where a level in decibels falls on either axis, between `0.0` and `1.0`.
+fn db_to_t(db: f32) -> f32 {
+    ((db - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0)
+}
+
+impl<LLevel, LThreshold, LCapacity, LFactor> View
+    for TransferCurve<LLevel, LThreshold, LCapacity, LFactor>
+where
+    LLevel: Lens<Target = f32>,
+    LThreshold: Lens<Target = f32>,
+    LCapacity: Lens<Target = f32>,
+    LFactor: Lens<Target = f32>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("transfer-curve")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let dpi_scale = cx.scale_factor();
+        let opacity = cx.opacity();
+        let mut background_color: vg::Color = cx.background_color().into();
+        background_color.set_alphaf(background_color.a * opacity);
+        let mut curve_color: vg::Color = cx.font_color().into();
+        curve_color.set_alphaf(curve_color.a * opacity);
+        let mut window_color = curve_color;
+        window_color.set_alphaf(curve_color.a * 0.15);
+        let mut unity_color: vg::Color = cx.border_color().into();
+        unity_color.set_alphaf(unity_color.a * opacity);
+
+        let x = |db: f32| bounds.x + db_to_t(db) * bounds.w;
+        let y = |db: f32| bounds.y + bounds.h - db_to_t(db) * bounds.h;
+        let bottom = bounds.y + bounds.h;
+
+        let mut path = vg::Path::new();
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&mut path, &vg::Paint::color(background_color));
+
+        let mut path = vg::Path::new();
+        path.move_to(x(MIN_DB), y(MIN_DB));
+        path.line_to(x(MAX_DB), y(MAX_DB));
+        let mut paint = vg::Paint::color(unity_color);
+        paint.set_line_width(dpi_scale);
+        canvas.stroke_path(&mut path, &paint);
+
+        // The plugin swaps the threshold and capacity if they cross, so the graph does the same
+        let threshold_db = util::gain_to_db(self.threshold.get(cx));
+        let capacity_db = util::gain_to_db(self.capacity.get(cx));
+        let (low_db, high_db) = (threshold_db.min(capacity_db), threshold_db.max(capacity_db));
+        let factor_db = util::gain_to_db(self.factor.get(cx));
+
+        let mut path = vg::Path::new();
+        path.rect(x(low_db), bounds.y, x(high_db) - x(low_db), bounds.h);
+        canvas.fill_path(&mut path, &vg::Paint::color(window_color));
+
+        // Muted signals are drawn along the bottom edge, since silence can't be plotted in decibels
+        let mut path = vg::Path::new();
+        path.move_to(bounds.x, bottom);
+        path.line_to(x(low_db), bottom);
+        path.line_to(x(low_db), y(low_db + factor_db));
+        path.line_to(x(high_db), y(high_db + factor_db));
+        path.line_to(x(high_db), bottom);
+        path.line_to(bounds.x + bounds.w, bottom);
+        let mut paint = vg::Paint::color(curve_color);
+        paint.set_line_width(1.5 * dpi_scale);
+        canvas.stroke_path(&mut path, &paint);
+
+        let level_db = util::gain_to_db(self.level.get(cx));
+        let output_y = if level_db > low_db && level_db < high_db {
+            y(level_db + factor_db)
+        } else {
+            bottom
+        };
+        let mut path = vg::Path::new();
+        path.circle(x(level_db), output_y, DOT_RADIUS * dpi_scale);
+        canvas.fill_path(&mut path, &vg::Paint::color(curve_color));
+    }
+}
@@ -0,0 +1,226 @@
+use nih_plug::prelude::{GuiContext, ParamPtr};
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::widgets::util::ModifiersExt;
+use nih_plug_vizia::widgets::RawParamEvent;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// The maximum number of entries kept in the undo history. The oldest entries are dropped first.
+const MAX_HISTORY_LEN: usize = 100;
+
+/// Records the parameter changes made from the editor so they can be undone and redone with
+/// Ctrl+Z and Ctrl+Shift+Z, or with [`UndoEvent`]s. Hosts often don't pick up edits made from a
+/// plugin's own GUI, so this can't be left to the host.
+///
+/// Changes are recorded per gesture from the events [`ParamWidgetBase`] emits, so dragging a knob
+/// results in a single entry holding the value from before and after the drag. Gestures that
+/// overlap, like dragging a node on the tap timeline which moves both the delay and the factor,
+/// are combined into a single entry. This model needs to be built at the root of the editor so it
+/// sees the events from every parameter widget.
+///
+/// Loading a preset replaces every parameter at once without going through these events, so the
+/// history is cleared with [`UndoEvent::Clear`] afterwards rather than undoing to values from
+/// before the preset was loaded.
+///
+/// [`ParamWidgetBase`]: nih_plug_vizia::widgets::param_base::ParamWidgetBase
+#[derive(Lens)]
+pub struct UndoHistory {
+    #[lens(ignore)]
+    gui_context: Arc<dyn GuiContext>,
+
+    /// The oldest entry is at the front so it can be dropped when the history gets too long.
+    #[lens(ignore)]
+    undo_stack: VecDeque<UndoEntry>,
+    #[lens(ignore)]
+    redo_stack: VecDeque<UndoEntry>,
+    /// The gestures that are currently in progress.
+    #[lens(ignore)]
+    active_gestures: HashMap<ParamPtr, ParamChange>,
+    /// Gestures that have ended while other gestures were still in progress. These are added to
+    /// the history as a single entry once the last gesture ends.
+    #[lens(ignore)]
+    finished_gestures: Vec<ParamChange>,
+
+    /// Whether there is anything to undo, used to disable the undo button.
+    can_undo: bool,
+    /// Whether there is anything to redo, used to disable the redo button.
+    can_redo: bool,
+}
+
+/// Undoes or redoes the last change. These are ignored while a parameter is being dragged.
+pub enum UndoEvent {
+    Undo,
+    Redo,
+    /// Forget the entire history, e.g. after a preset has been loaded.
+    Clear,
+}
+
+/// One or more parameter changes that are undone and redone together.
+struct UndoEntry {
+    changes: Vec<ParamChange>,
+}
+
+/// A single parameter's normalized value before and after a gesture.
+#[derive(Clone, Copy)]
+struct ParamChange {
+    param: ParamPtr,
+    before: f32,
+    after: f32,
+}
+
+impl UndoHistory {
+    /// Creates an empty history. `gui_context` is used to apply the undone and redone changes.
+    pub fn new(gui_context: Arc<dyn GuiContext>) -> Self {
+        Self {
+            gui_context,
+
+            undo_stack: VecDeque::new(),
+            redo_stack: VecDeque::new(),
+            active_gestures: HashMap::new(),
+            finished_gestures: Vec::new(),
+
+            can_undo: false,
+            can_redo: false,
+        }
+    }
+
+    fn begin_gesture(&mut self, param: ParamPtr) {
+        // SAFETY: The parameter outlives the editor, `ParamWidgetBase` relies on the same
+        //         guarantee
+        let value = unsafe { param.unmodulated_normalized_value() };
+        self.active_gestures.entry(param).or_insert(ParamChange {
+            param,
+            before: value,
+            after: value,
+        });
+    }
+
+    fn end_gesture(&mut self, param: ParamPtr) {
+        let Some(change) = self.active_gestures.remove(&param) else {
+            return;
+        };
+
+        // Clicking a widget without moving it also results in a gesture
+        if change.after != change.before {
+            self.finished_gestures.push(change);
+        }
+        if self.active_gestures.is_empty() && !self.finished_gestures.is_empty() {
+            let changes = std::mem::take(&mut self.finished_gestures);
+            self.undo_stack.push_back(UndoEntry { changes });
+            if self.undo_stack.len() > MAX_HISTORY_LEN {
+                self.undo_stack.pop_front();
+            }
+            self.redo_stack.clear();
+            self.update_flags();
+        }
+    }
+
+    fn undo(&mut self, cx: &mut EventContext) {
+        if !self.active_gestures.is_empty() {
+            return;
+        }
+        let Some(entry) = self.undo_stack.pop_back() else {
+            return;
+        };
+
+        for change in entry.changes.iter().rev() {
+            self.set_parameter(change.param, change.before);
+        }
+        self.redo_stack.push_back(entry);
+        self.update_flags();
+        notify_parameters_changed(cx);
+    }
+
+    fn redo(&mut self, cx: &mut EventContext) {
+        if !self.active_gestures.is_empty() {
+            return;
+        }
+        let Some(entry) = self.redo_stack.pop_back() else {
+            return;
+        };
+
+        for change in &entry.changes {
+            self.set_parameter(change.param, change.after);
+        }
+        self.undo_stack.push_back(entry);
+        self.update_flags();
+        notify_parameters_changed(cx);
+    }
+
+    fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.active_gestures.clear();
+        self.finished_gestures.clear();
+        self.update_flags();
+    }
+
+    /// Set a parameter directly through the GUI context. This doesn't go through the editor's
+    /// parameter events, so the change doesn't end up in the history again.
+    fn set_parameter(&self, param: ParamPtr, normalized: f32) {
+        // SAFETY: The parameter outlives the editor, `ParamWidgetBase` relies on the same
+        //         guarantee
+        unsafe {
+            self.gui_context.raw_begin_set_parameter(param);
+            self.gui_context
+                .raw_set_parameter_normalized(param, normalized);
+            self.gui_context.raw_end_set_parameter(param);
+        }
+    }
+
+    fn update_flags(&mut self) {
+        self.can_undo = !self.undo_stack.is_empty();
+        self.can_redo = !self.redo_stack.is_empty();
+    }
+}
+
+/// Let the editor's widgets know that parameter values have changed outside of their own gestures.
+/// This is the same event the wrapper sends when the host changes a parameter.
+fn notify_parameters_changed(cx: &mut EventContext) {
+    cx.emit_custom(
+        Event::new(RawParamEvent::ParametersChanged)
+            .target(Entity::root())
+            .propagate(Propagation::Subtree),
+    );
+}
+
+impl Model for UndoHistory {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        // These events are only observed, the editor's parameter model still needs to handle them
+        event.map(|param_event, _| match param_event {
+            RawParamEvent::BeginSetParameter(param) => self.begin_gesture(*param),
+            RawParamEvent::SetParameterNormalized(param, value) => {
+                if let Some(change) = self.active_gestures.get_mut(param) {
+                    change.after = *value;
+                }
+            }
+            RawParamEvent::EndSetParameter(param) => self.end_gesture(*param),
+            _ => (),
+        });
+
+        event.map(|undo_event, meta| {
+            match undo_event {
+                UndoEvent::Undo => self.undo(cx),
+                UndoEvent::Redo => self.redo(cx),
+                UndoEvent::Clear => self.clear(),
+            }
+
+            meta.consume();
+        });
+
+        event.map(|window_event, meta| {
+            if let WindowEvent::KeyDown(Code::KeyZ, _) = window_event {
+                // This is Cmd+Z on macOS
+                if cx.modifiers.command() {
+                    if cx.modifiers.shift() {
+                        self.redo(cx);
+                    } else {
+                        self.undo(cx);
+                    }
+
+                    meta.consume();
+                }
+            }
+        });
+    }
+}
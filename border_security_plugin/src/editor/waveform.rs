@@ -0,0 +1,110 @@
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::sync::{Arc, Mutex};
+
+use super::tap_color;
+use crate::waveform::{WaveformOutput, WAVEFORM_COLUMNS};
+
+/// A scrolling waveform of the delay line. The newest audio is on the right and the oldest audio
+/// is on the left. Every bucket's read position is drawn as a vertical marker, and the window
+/// between its threshold and capacity is drawn as a horizontal band. The band is only drawn above
+/// the zero line because the gate never lets negative samples through. The waveform uses the font
+/// color, the zero line uses the border color, and the background is filled with the background
+/// color.
+pub struct Waveform {
+    waveform: Arc<Mutex<WaveformOutput>>,
+}
+
+impl Waveform {
+    /// Creates a new [`Waveform`] that reads the delay line's contents from `waveform`.
+    pub fn new<LWaveform>(cx: &mut Context, waveform: LWaveform) -> Handle<Self>
+    where
+        LWaveform: Lens<Target = Arc<Mutex<WaveformOutput>>>,
+    {
+        Self {
+            waveform: waveform.get(cx),
+        }
+        .build(cx, |_| {})
+    }
+}
+
+impl View for Waveform {
+    fn element(&self) -> Option<&'static str> {
+        Some("waveform")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        // This only ever gets locked from the GUI thread, the audio thread writes to the other end
+        // of the triple buffer
+        let mut waveform = self.waveform.lock().unwrap();
+        let waveform = waveform.read();
+
+        let dpi_scale = cx.scale_factor();
+        let opacity = cx.opacity();
+        let mut waveform_color: vg::Color = cx.font_color().into();
+        waveform_color.set_alphaf(waveform_color.a * opacity);
+        let mut axis_color: vg::Color = cx.border_color().into();
+        axis_color.set_alphaf(axis_color.a * opacity);
+        let mut background_color: vg::Color = cx.background_color().into();
+        background_color.set_alphaf(background_color.a * opacity);
+
+        let mut path = vg::Path::new();
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&mut path, &vg::Paint::color(background_color));
+
+        // Amplitudes are drawn linearly, with full scale reaching the top and bottom edges
+        let center_y = bounds.y + bounds.h / 2.0;
+        let amplitude_to_y =
+            |amplitude: f32| center_y - amplitude.clamp(-1.0, 1.0) * bounds.h / 2.0;
+
+        let mut path = vg::Path::new();
+        path.move_to(bounds.x, center_y);
+        path.line_to(bounds.x + bounds.w, center_y);
+        let mut paint = vg::Paint::color(axis_color);
+        paint.set_line_width(dpi_scale);
+        canvas.stroke_path(&mut path, &paint);
+
+        // The gate windows go below the waveform so it stays readable
+        for (bucket, tap) in waveform.taps.iter().enumerate() {
+            let band_color = tap_color(bucket, 0.2 * opacity);
+
+            let top = amplitude_to_y(tap.capacity);
+            let bottom = amplitude_to_y(tap.threshold);
+            let mut path = vg::Path::new();
+            path.rect(bounds.x, top, bounds.w, bottom - top);
+            canvas.fill_path(&mut path, &vg::Paint::color(band_color));
+        }
+
+        // Every column is drawn as a vertical line from its minimum to its maximum, with the
+        // newest column on the right
+        let column_width = bounds.w / WAVEFORM_COLUMNS as f32;
+        let mut path = vg::Path::new();
+        for columns_ago in 0..WAVEFORM_COLUMNS {
+            let (min, max) = waveform.column(columns_ago);
+            let x = bounds.x + bounds.w - (columns_ago as f32 + 0.5) * column_width;
+            path.move_to(x, amplitude_to_y(max));
+            // Silent columns would otherwise not be drawn at all
+            path.line_to(x, amplitude_to_y(min) + 0.5 * dpi_scale);
+        }
+        let mut paint = vg::Paint::color(waveform_color);
+        paint.set_line_width(column_width.max(dpi_scale));
+        canvas.stroke_path(&mut path, &paint);
+
+        for (bucket, tap) in waveform.taps.iter().enumerate() {
+            let marker_color = tap_color(bucket, opacity);
+
+            let x = bounds.x + bounds.w - tap.position.clamp(0.0, 1.0) * bounds.w;
+            let mut path = vg::Path::new();
+            path.move_to(x, bounds.y);
+            path.line_to(x, bounds.y + bounds.h);
+            let mut paint = vg::Paint::color(marker_color);
+            paint.set_line_width(2.0 * dpi_scale);
+            canvas.stroke_path(&mut path, &paint);
+        }
+    }
+}
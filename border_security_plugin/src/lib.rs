@@ -1,23 +1,85 @@
 use array_init::array_init;
-use circular_buffer::CircleBuffer;
+use atomic_float::AtomicF32;
+use delay_line::DelayLine;
+use meters::BucketMeter;
 use nih_plug::{params::Param, prelude::*};
 use nih_plug_vizia::ViziaState;
-use std::{cell::RefCell, sync::Arc};
-
+use spectrum::{SpectrumFrame, SpectrumInput, SpectrumOutput};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use waveform::{WaveformInput, WaveformOutput, WaveformTap};
+use widgets::level_meter::{LevelMeasurement, LevelMeterData};
+
+// The plugin only uses the parts of the ring buffer's API that the delay line needs
+#[allow(dead_code)]
 mod circular_buffer;
+mod delay_line;
 mod editor;
+mod meters;
+mod preset_file;
+mod presets;
+mod spectrum;
+mod state_migration;
+#[cfg(test)]
+mod test_util;
+mod waveform;
 
 // This is a shortened version of the gain example with most comments removed, check out
 // https://github.com/robbert-vdh/nih-plug/blob/master/plugins/examples/gain/src/lib.rs to get
 // started
 
 const MAX_DELAY: usize = 2;
+#[cfg(not(feature = "bench"))]
 const BUCKETS: usize = 2;
+/// The benchmarks measure how processing scales with the number of buckets, so they use a lot more
+/// of them.
+#[cfg(feature = "bench")]
+const BUCKETS: usize = 16;
+/// The audio is processed in blocks of at most this many samples. The smoothed parameter values
+/// for each bucket are computed once per block instead of once per sample per channel.
+const MAX_BLOCK_SIZE: usize = 64;
 
 pub struct BorderSecurityPlugin {
     params: Arc<BorderSecurityPluginParams>,
-    //Channel - Buckets - Delay Buffer
-    delay_buffers: Vec<RefCell<CircleBuffer>>,
+    /// The input audio for all channels, shared by every bucket.
+    delay_line: DelayLine,
+    /// Scratch buffers for the per-bucket parameter values of the block that's currently being
+    /// processed. These are preallocated so `process()` doesn't need to allocate.
+    bucket_values: [BucketValues; BUCKETS],
+
+    /// Downsamples the delay line's contents for the editor's waveform view.
+    waveform: WaveformInput,
+    /// The editor's end of `waveform`. The mutex is only ever locked from the GUI thread.
+    waveform_output: Arc<Mutex<WaveformOutput>>,
+
+    /// What each bucket's gate is doing, shown in the editor.
+    bucket_meters: Arc<[BucketMeter; BUCKETS]>,
+    /// The per sample decay weight for the meters at the current sample rate.
+    meter_decay_weight: f32,
+
+    /// The levels going into and coming out of the plugin, shown in the editor.
+    input_level: Arc<LevelMeterData>,
+    output_level: Arc<LevelMeterData>,
+    input_measurement: LevelMeasurement,
+    output_measurement: LevelMeasurement,
+
+    /// Sends the input, wet, and output signals to the editor's spectrum analyzer.
+    spectrum: SpectrumInput,
+    /// The editor's end of `spectrum`. The mutex is only ever locked from the GUI thread.
+    spectrum_output: Arc<Mutex<SpectrumOutput>>,
+
+    /// The host's tempo in beats per minute, or `0.0` until the host reports one. This is shared
+    /// with the editor for the widgets' beat based time formats and snapping grids.
+    tempo: Arc<AtomicF32>,
+}
+
+/// The smoothed parameter values for a single bucket over the course of one block, with the
+/// delay time already converted to a read offset in the delay buffer.
+struct BucketValues {
+    read_offsets: [usize; MAX_BLOCK_SIZE],
+    thresholds: [f32; MAX_BLOCK_SIZE],
+    capacities: [f32; MAX_BLOCK_SIZE],
+    factors: [f32; MAX_BLOCK_SIZE],
 }
 
 #[derive(Params)]
@@ -48,54 +110,95 @@ pub struct DelayParam {
 
 impl Default for BorderSecurityPlugin {
     fn default() -> Self {
+        let (waveform, waveform_output) = WaveformInput::new();
+        let input_level = Arc::new(LevelMeterData::default());
+        let output_level = Arc::new(LevelMeterData::default());
+        let (spectrum, spectrum_output) = SpectrumInput::new();
+
         Self {
             params: Arc::new(BorderSecurityPluginParams::default()),
-            delay_buffers: Vec::new(),
+            delay_line: DelayLine::new(),
+            bucket_values: array_init(|_| BucketValues::default()),
+
+            waveform,
+            waveform_output: Arc::new(Mutex::new(waveform_output)),
+
+            bucket_meters: Arc::new(array_init(|_| BucketMeter::default())),
+            meter_decay_weight: 1.0,
+
+            input_measurement: LevelMeasurement::new(input_level.clone()),
+            output_measurement: LevelMeasurement::new(output_level.clone()),
+            input_level,
+            output_level,
+
+            spectrum,
+            spectrum_output: Arc::new(Mutex::new(spectrum_output)),
+
+            tempo: Arc::new(AtomicF32::new(0.0)),
+        }
+    }
+}
+
+impl Default for BucketValues {
+    fn default() -> Self {
+        Self {
+            read_offsets: [0; MAX_BLOCK_SIZE],
+            thresholds: [0.0; MAX_BLOCK_SIZE],
+            capacities: [0.0; MAX_BLOCK_SIZE],
+            factors: [0.0; MAX_BLOCK_SIZE],
         }
     }
 }
 
 impl Default for BorderSecurityPluginParams {
     fn default() -> Self {
-        let delay_params: [DelayParam; BUCKETS] = array_init(|index| DelayParam {
-            delay: FloatParam::new(
-                format!("Delay {index}"),
-                0.0,
-                FloatRange::Linear {
-                    min: 0.0,
-                    max: MAX_DELAY as f32,
-                },
-            ),
-            threshold: FloatParam::new(
-                format!("Threshold {index}"),
-                util::db_to_gain(-30.0),
-                FloatRange::Skewed {
-                    min: util::db_to_gain(-30.0),
-                    max: util::db_to_gain(30.0),
-                    // This makes the range appear as if it was linear when displaying the values as
-                    // decibels
-                    factor: FloatRange::gain_skew_factor(-30.0, 30.0),
-                },
-            ),
-            capacity: FloatParam::new(
-                format!("Threshold {index}"),
-                util::db_to_gain(30.0),
-                FloatRange::Skewed {
-                    min: util::db_to_gain(-30.0),
-                    max: util::db_to_gain(30.0),
-                    // This makes the range appear as if it was linear when displaying the values as
-                    // decibels
-                    factor: FloatRange::gain_skew_factor(-30.0, 30.0),
-                },
-            ),
-            factor: FloatParam::new(
-                format!("Threshold {index}"),
-                util::db_to_gain(0.0),
-                FloatRange::Linear {
-                    min: util::db_to_gain(-1.0),
-                    max: util::db_to_gain(1.0),
-                },
-            ),
+        let delay_params: [DelayParam; BUCKETS] = array_init(|index| {
+            // Matches the 1-based suffix `#[nested(array)]` adds to the parameter IDs
+            let number = index + 1;
+            DelayParam {
+                delay: FloatParam::new(
+                    format!("Delay {number}"),
+                    0.0,
+                    FloatRange::Linear {
+                        min: 0.0,
+                        max: MAX_DELAY as f32,
+                    },
+                )
+                .with_smoother(SmoothingStyle::Linear(50.0)),
+                threshold: FloatParam::new(
+                    format!("Threshold {number}"),
+                    util::db_to_gain(-30.0),
+                    FloatRange::Skewed {
+                        min: util::db_to_gain(-30.0),
+                        max: util::db_to_gain(30.0),
+                        // This makes the range appear as if it was linear when displaying the values as
+                        // decibels
+                        factor: FloatRange::gain_skew_factor(-30.0, 30.0),
+                    },
+                )
+                .with_smoother(SmoothingStyle::Logarithmic(50.0)),
+                capacity: FloatParam::new(
+                    format!("Capacity {number}"),
+                    util::db_to_gain(30.0),
+                    FloatRange::Skewed {
+                        min: util::db_to_gain(-30.0),
+                        max: util::db_to_gain(30.0),
+                        // This makes the range appear as if it was linear when displaying the values as
+                        // decibels
+                        factor: FloatRange::gain_skew_factor(-30.0, 30.0),
+                    },
+                )
+                .with_smoother(SmoothingStyle::Logarithmic(50.0)),
+                factor: FloatParam::new(
+                    format!("Factor {number}"),
+                    util::db_to_gain(0.0),
+                    FloatRange::Linear {
+                        min: util::db_to_gain(-1.0),
+                        max: util::db_to_gain(1.0),
+                    },
+                )
+                .with_smoother(SmoothingStyle::Linear(50.0)),
+            }
         });
         Self {
             editor_state: editor::default_state(),
@@ -104,6 +207,226 @@ impl Default for BorderSecurityPluginParams {
     }
 }
 
+impl BorderSecurityPlugin {
+    /// Size the plugin's buffers for `num_channels` channels at `sample_rate`. This is what
+    /// `initialize()` does, and it's public so the benchmarks can set up the plugin without a host.
+    pub fn resize(&mut self, num_channels: usize, sample_rate: f32) {
+        self.delay_line.resize(num_channels, sample_rate, MAX_DELAY);
+        self.waveform.resize(sample_rate);
+        self.meter_decay_weight = meters::meter_decay_weight(sample_rate);
+        self.input_measurement.set_sample_rate(sample_rate);
+        self.output_measurement.set_sample_rate(sample_rate);
+        self.spectrum.set_sample_rate(sample_rate);
+    }
+
+    /// Everything `process()` does besides reading the host's transport. This is public so the
+    /// benchmarks can process audio without a host.
+    pub fn process_buffer(&mut self, buffer: &mut Buffer) -> ProcessStatus {
+        // `initialize()` sizes the delay line and `deactivate()` frees it again, so this only
+        // happens when the host processes audio without initializing the plugin first
+        if self.delay_line.frames() == 0 {
+            nih_debug_assert_failure!("process() was called before initialize()");
+            return ProcessStatus::Normal;
+        }
+
+        for (_, mut block) in buffer.iter_blocks(MAX_BLOCK_SIZE) {
+            let block_len = block.samples();
+
+            // The delay times can be converted to read offsets before touching any audio
+            let offset_scale = (self.delay_line.frames() - 1) as f32 / MAX_DELAY as f32;
+
+            let mut delay_lengths = [0.0; MAX_BLOCK_SIZE];
+            for (delay_param, bucket_values) in self
+                .params
+                .delay_params
+                .iter()
+                .zip(self.bucket_values.iter_mut())
+            {
+                delay_param
+                    .delay
+                    .smoothed
+                    .next_block(&mut delay_lengths, block_len);
+                delay_param
+                    .threshold
+                    .smoothed
+                    .next_block(&mut bucket_values.thresholds, block_len);
+                delay_param
+                    .capacity
+                    .smoothed
+                    .next_block(&mut bucket_values.capacities, block_len);
+                delay_param
+                    .factor
+                    .smoothed
+                    .next_block(&mut bucket_values.factors, block_len);
+
+                // The editor keeps the threshold below the capacity, but automation can still make
+                // them cross. The window between them is the same either way.
+                for (threshold, capacity) in bucket_values.thresholds[..block_len]
+                    .iter_mut()
+                    .zip(&mut bucket_values.capacities[..block_len])
+                {
+                    if *threshold > *capacity {
+                        std::mem::swap(threshold, capacity);
+                    }
+                }
+
+                for (read_offset, delay_length) in bucket_values.read_offsets[..block_len]
+                    .iter_mut()
+                    .zip(&delay_lengths[..block_len])
+                {
+                    *read_offset = (delay_length * offset_scale) as usize;
+                }
+            }
+
+            // The peak level each bucket's gate saw and the number of samples it let through
+            let mut bucket_peaks = [0.0f32; BUCKETS];
+            let mut bucket_open_samples = [0usize; BUCKETS];
+            // There's no point in feeding the analyzer and the waveform when nothing reads them
+            let editor_open = self.params.editor_state.is_open();
+
+            for (sample_idx, mut channel_samples) in block.iter_samples().enumerate() {
+                self.input_measurement
+                    .add_frame(channel_samples.iter_mut().map(|sample| *sample));
+                self.delay_line
+                    .write_frame(channel_samples.iter_mut().map(|sample| *sample));
+                if editor_open {
+                    self.waveform.push_frame(self.delay_line.read_frame(0));
+                }
+
+                let mut spectrum_frame = SpectrumFrame::default();
+                for (i, sample) in channel_samples.iter_mut().enumerate() {
+                    spectrum_frame.input += *sample;
+                    let crossfade_factor = 0.5;
+                    let mut wet_sample = 0.0;
+
+                    for (bucket, bucket_values) in self.bucket_values.iter().enumerate() {
+                        let threshold = bucket_values.thresholds[sample_idx];
+                        let capacity = bucket_values.capacities[sample_idx];
+
+                        let delayed_sample = self
+                            .delay_line
+                            .read(i, bucket_values.read_offsets[sample_idx]);
+                        // The gate compares the signed sample against its window, so negative
+                        // samples never get through. The meter only shows the positive peaks
+                        // for the same reason.
+                        bucket_peaks[bucket] = bucket_peaks[bucket].max(delayed_sample);
+                        if delayed_sample > threshold && delayed_sample < capacity {
+                            wet_sample += delayed_sample * bucket_values.factors[sample_idx];
+                            bucket_open_samples[bucket] += 1;
+                        }
+                    }
+                    *sample = *sample * (1.0 - crossfade_factor) + wet_sample * crossfade_factor;
+
+                    spectrum_frame.wet += wet_sample;
+                    spectrum_frame.output += *sample;
+                }
+
+                self.output_measurement
+                    .add_frame(channel_samples.iter_mut().map(|sample| *sample));
+
+                if editor_open {
+                    let num_channels = channel_samples.len().max(1) as f32;
+                    spectrum_frame.input /= num_channels;
+                    spectrum_frame.wet /= num_channels;
+                    spectrum_frame.output /= num_channels;
+                    self.spectrum.push(spectrum_frame);
+                }
+            }
+
+            // These are always measured so the clip indicators also catch clipping that happened
+            // while the editor was closed
+            self.input_measurement.publish();
+            self.output_measurement.publish();
+
+            // The waveform display and the meters are only updated while the editor is open
+            if editor_open {
+                // The editor only needs to know where the taps ended up at the end of the block
+                let last_sample_idx = block_len - 1;
+                for (bucket, bucket_values) in self.bucket_values.iter().enumerate() {
+                    self.waveform.set_tap(
+                        bucket,
+                        WaveformTap {
+                            position: bucket_values.read_offsets[last_sample_idx] as f32
+                                / (self.delay_line.frames() - 1).max(1) as f32,
+                            threshold: bucket_values.thresholds[last_sample_idx],
+                            capacity: bucket_values.capacities[last_sample_idx],
+                        },
+                    );
+                }
+                self.waveform.publish();
+
+                let num_channels = self.delay_line.channels().max(1);
+                for ((bucket_meter, peak), open_samples) in self
+                    .bucket_meters
+                    .iter()
+                    .zip(bucket_peaks)
+                    .zip(bucket_open_samples)
+                {
+                    let activity = open_samples as f32 / (block_len * num_channels) as f32;
+                    meters::update_meter(
+                        &bucket_meter.level,
+                        peak,
+                        self.meter_decay_weight,
+                        block_len,
+                    );
+                    meters::update_meter(
+                        &bucket_meter.activity,
+                        activity,
+                        self.meter_decay_weight,
+                        block_len,
+                    );
+                }
+            }
+        }
+
+        ProcessStatus::Normal
+    }
+
+    /// The per sample processing from before the parameters were smoothed in blocks, where every
+    /// bucket calls `.value()` on all of its parameters for every sample of every channel. This is
+    /// only kept as the baseline for the benchmarks. It doesn't update the meters, the waveform, or
+    /// the spectrum analyzer, so if anything the comparison is skewed in this version's favor.
+    #[cfg(feature = "bench")]
+    pub fn process_buffer_per_sample(&mut self, buffer: &mut Buffer) -> ProcessStatus {
+        if self.delay_line.frames() == 0 {
+            nih_debug_assert_failure!("process() was called before initialize()");
+            return ProcessStatus::Normal;
+        }
+
+        let offset_scale = (self.delay_line.frames() - 1) as f32 / MAX_DELAY as f32;
+        for mut channel_samples in buffer.iter_samples() {
+            self.delay_line
+                .write_frame(channel_samples.iter_mut().map(|sample| *sample));
+
+            for (i, sample) in channel_samples.iter_mut().enumerate() {
+                let crossfade_factor = 0.5;
+                let mut wet_sample = 0.0;
+
+                for delay_param in &self.params.delay_params {
+                    let delay_length = delay_param.delay.value();
+                    let threshold = delay_param.threshold.value();
+                    let capacity = delay_param.capacity.value();
+                    let factor = delay_param.factor.value();
+                    let (threshold, capacity) = if threshold > capacity {
+                        (capacity, threshold)
+                    } else {
+                        (threshold, capacity)
+                    };
+
+                    let read_offset = (delay_length * offset_scale) as usize;
+                    let delayed_sample = self.delay_line.read(i, read_offset);
+                    if delayed_sample > threshold && delayed_sample < capacity {
+                        wet_sample += delayed_sample * factor;
+                    }
+                }
+                *sample = *sample * (1.0 - crossfade_factor) + wet_sample * crossfade_factor;
+            }
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
 impl Plugin for BorderSecurityPlugin {
     const NAME: &'static str = "Border Security Plugin";
     const VENDOR: &'static str = "ActuallyAdequate";
@@ -152,69 +475,66 @@ impl Plugin for BorderSecurityPlugin {
         _buffer_config: &BufferConfig,
         _context: &mut impl InitContext<Self>,
     ) -> bool {
-        // Resize buffers and perform other potentially expensive initialization operations here.
-        // The `reset()` function is always called right after this function. You can remove this
-        // function if you do not need it.
+        // This is also called again when the host changes the sample rate or the channel layout,
+        // in which case the delay line gets reallocated from scratch. The `reset()` function is
+        // always called right after this function.
         let output_channels = _audio_io_layout
             .main_output_channels
             .expect("Plugin does not have main output channels!")
             .get() as usize;
 
-        self.delay_buffers.resize_with(output_channels, || {
-            let mut delay_buffer = CircleBuffer::new();
-            delay_buffer.resize(_buffer_config.sample_rate, MAX_DELAY);
-            RefCell::new(delay_buffer)
-        });
+        self.resize(output_channels, _buffer_config.sample_rate);
 
         true
     }
 
     fn reset(&mut self) {
-        // Reset buffers and envelopes here. This can be called from the audio thread and may not
-        // allocate. You can remove this function if you do not need it.
+        // This can be called from the audio thread, so nothing in here may allocate
+        self.delay_line.clear();
+        self.waveform.clear();
+        self.input_measurement.reset();
+        self.output_measurement.reset();
+
+        // Otherwise the smoothers would still be ramping towards values from before the reset
+        for delay_param in &self.params.delay_params {
+            for param in [
+                &delay_param.delay,
+                &delay_param.threshold,
+                &delay_param.capacity,
+                &delay_param.factor,
+            ] {
+                param.smoothed.reset(param.value());
+            }
+        }
     }
 
     fn process(
         &mut self,
         buffer: &mut Buffer,
         _aux: &mut AuxiliaryBuffers,
-        _context: &mut impl ProcessContext<Self>,
+        context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        for (i, channel) in buffer.as_slice().iter_mut().enumerate() {
-            let mut delay_buffer = self.delay_buffers[i].borrow_mut();
-
-            for sample in channel.iter_mut() {
-                let crossfade_factor = 0.5;
-                let mut wet_sample = 0.0;
-
-                delay_buffer.write(*sample);
-
-                for j in 0..BUCKETS {
-                    let delay_length = self.params.delay_params[j].delay.value();
-                    let threshold = self.params.delay_params[j].threshold.value();
-                    let capacity = self.params.delay_params[j].capacity.value();
-                    let factor = self.params.delay_params[j].factor.value();
-
-                    let read_offset = (delay_length / MAX_DELAY as f32
-                        * ((delay_buffer.samples() - 1) as f32))
-                        as usize;
-                    let delayed_sample = delay_buffer.read(read_offset);
-                    let delay_factor = if delayed_sample > threshold && delayed_sample < capacity {
-                        factor
-                    } else {
-                        0.0
-                    };
-                    wet_sample += delayed_sample * delay_factor;
-                }
-                *sample = *sample * (1.0 - crossfade_factor) + wet_sample * crossfade_factor;
-            }
+        // The last known tempo is kept when the host stops reporting one
+        if let Some(tempo) = context.transport().tempo {
+            self.tempo.store(tempo as f32, Ordering::Relaxed);
         }
 
-        ProcessStatus::Normal
+        self.process_buffer(buffer)
     }
 
     fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
-        editor::create(self.params.clone(), self.params.editor_state.clone())
+        editor::create(
+            editor::Data {
+                params: self.params.clone(),
+                waveform: self.waveform_output.clone(),
+                bucket_meters: self.bucket_meters.clone(),
+                input_level: self.input_level.clone(),
+                output_level: self.output_level.clone(),
+                spectrum: self.spectrum_output.clone(),
+                tempo: self.tempo.clone(),
+            },
+            self.params.editor_state.clone(),
+        )
     }
 
     fn task_executor(&mut self) -> TaskExecutor<Self> {
@@ -222,9 +542,15 @@ impl Plugin for BorderSecurityPlugin {
         Box::new(|_| ())
     }
 
-    fn filter_state(state: &mut PluginState) {}
+    fn filter_state(state: &mut PluginState) {
+        state_migration::migrate(state);
+    }
 
-    fn deactivate(&mut self) {}
+    fn deactivate(&mut self) {
+        // `initialize()` is always called again before processing resumes, so the delay line's
+        // memory doesn't need to stick around in the meantime
+        self.delay_line.free();
+    }
 }
 
 impl ClapPlugin for BorderSecurityPlugin {
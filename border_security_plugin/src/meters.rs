@@ -0,0 +1,37 @@
+//! Levels measured on the audio thread and shown by the editor's meters.
+
+use atomic_float::AtomicF32;
+use std::sync::atomic::Ordering;
+use widgets::level_meter;
+
+/// How long it takes for a meter to fall back to 25% of its value after the signal stops.
+pub const METER_DECAY_MS: f64 = 150.0;
+
+/// What a single bucket's gate is doing. These are only updated while the editor is open.
+#[derive(Default)]
+pub struct BucketMeter {
+    /// The peak level of the delayed signal's positive samples the bucket's gate sees, as a linear
+    /// gain. Negative samples never pass the gate, so they don't count towards this level.
+    pub level: AtomicF32,
+    /// How much of the time the gate was letting the signal through, between `0.0` and `1.0`.
+    pub activity: AtomicF32,
+}
+
+/// The per sample weight for [`update_meter()`] at `sample_rate`. This uses the same decay as the
+/// level meters' peaks.
+pub fn meter_decay_weight(sample_rate: f32) -> f32 {
+    level_meter::peak_decay_weight(sample_rate, METER_DECAY_MS)
+}
+
+/// Update `meter` with a newly measured `value` covering `num_samples` samples. Rising values are
+/// shown immediately and falling values decay using `decay_weight`, which comes from
+/// [`meter_decay_weight()`].
+pub fn update_meter(meter: &AtomicF32, value: f32, decay_weight: f32, num_samples: usize) {
+    let new_value = level_meter::decay_peak(
+        meter.load(Ordering::Relaxed),
+        value,
+        decay_weight,
+        num_samples,
+    );
+    meter.store(new_value, Ordering::Relaxed);
+}
@@ -0,0 +1,259 @@
+//! A human readable preset format that can be exported to and imported from JSON or TOML files.
+//! Values are stored in the same units the editor displays, so the files are easy to edit by hand
+//! and produce readable diffs when kept in version control. User presets are stored in this format
+//! as well.
+
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::ParamValue;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{state_migration, BUCKETS};
+
+/// All of the plugin's parameters. Every field is optional when reading a file, and missing values
+/// leave the corresponding parameters unchanged. Unknown fields are ignored, so files written by
+/// newer versions of the plugin can still be imported.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresetFile {
+    /// The version of the plugin that wrote the file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// One entry per bucket, in order. The plugin currently doesn't have any global parameters
+    /// besides the buckets.
+    pub buckets: Vec<PresetFileBucket>,
+}
+
+/// A single bucket's parameters in a [`PresetFile`].
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresetFileBucket {
+    /// In seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<f32>,
+    /// In decibels.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold_db: Option<f32>,
+    /// In decibels.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capacity_db: Option<f32>,
+    /// In decibels.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub factor_db: Option<f32>,
+}
+
+impl PresetFile {
+    /// Read the parameter values from the plugin's state.
+    pub fn from_state(state: &PluginState) -> Self {
+        let read = |id: String| match state.params.get(&id) {
+            Some(ParamValue::F32(value)) => Some(*value),
+            _ => None,
+        };
+
+        Self {
+            version: Some(env!("CARGO_PKG_VERSION").to_owned()),
+            buckets: (1..=BUCKETS)
+                .map(|suffix| PresetFileBucket {
+                    delay: read(format!("delay_{suffix}")),
+                    threshold_db: read(format!("threshold_{suffix}")).map(util::gain_to_db),
+                    capacity_db: read(format!("capacity_{suffix}")).map(util::gain_to_db),
+                    factor_db: read(format!("factor_{suffix}")).map(util::gain_to_db),
+                })
+                .collect(),
+        }
+    }
+
+    /// Write the values from this file to the plugin's state. Values missing from the file and
+    /// buckets the plugin doesn't have are skipped.
+    ///
+    /// The values go through the same migrations as the plugin's own state, based on the file's
+    /// version. Files without a version are assumed to match the current layout, since the version
+    /// is optional like every other field and is most likely missing from a file written by hand.
+    pub fn apply_to_state(&self, state: &mut PluginState) {
+        let mut preset_state = PluginState {
+            version: self
+                .version
+                .clone()
+                .unwrap_or_else(|| env!("CARGO_PKG_VERSION").to_owned()),
+            params: BTreeMap::new(),
+            fields: BTreeMap::new(),
+        };
+
+        // The `#[nested(array)]` attribute suffixes the IDs with the 1-based bucket index
+        for (bucket, suffix) in self.buckets.iter().zip(1..) {
+            let values = [
+                ("delay", bucket.delay),
+                ("threshold", bucket.threshold_db.map(util::db_to_gain)),
+                ("capacity", bucket.capacity_db.map(util::db_to_gain)),
+                ("factor", bucket.factor_db.map(util::db_to_gain)),
+            ];
+            for (id, value) in values {
+                let Some(value) = value else {
+                    continue;
+                };
+
+                preset_state
+                    .params
+                    .insert(format!("{id}_{suffix}"), ParamValue::F32(value));
+            }
+        }
+
+        // Buckets the plugin doesn't have are only dropped after migrating, since a migration may
+        // still move their values elsewhere. Only the parameters are copied over, the state's
+        // other fields like the editor size are left alone.
+        state_migration::migrate(&mut preset_state);
+        state.params.extend(
+            preset_state
+                .params
+                .into_iter()
+                .filter(|(id, _)| !is_extra_bucket_param(id)),
+        );
+    }
+
+    /// Read a preset file. Files ending in `.toml` are parsed as TOML, and everything else is
+    /// parsed as JSON.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?, is_toml(path))
+    }
+
+    /// Write this preset to a file, using the same format detection as [`read()`][Self::read()].
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.serialize(is_toml(path))?)
+    }
+
+    /// Parse a preset from TOML if `toml` is set, or from JSON otherwise.
+    fn parse(contents: &str, toml: bool) -> io::Result<Self> {
+        if toml {
+            toml::from_str(contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        } else {
+            serde_json::from_str(contents)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        }
+    }
+
+    /// Serialize this preset to TOML if `toml` is set, or to JSON otherwise.
+    fn serialize(&self, toml: bool) -> io::Result<String> {
+        if toml {
+            toml::to_string_pretty(self)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        } else {
+            serde_json::to_string_pretty(self)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        }
+    }
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"))
+}
+
+/// Whether `id` belongs to a bucket the plugin doesn't have, e.g. `delay_3` with two buckets.
+fn is_extra_bucket_param(id: &str) -> bool {
+    id.rsplit_once('_')
+        .and_then(|(_, suffix)| suffix.parse::<usize>().ok())
+        .is_some_and(|suffix| suffix > BUCKETS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_params_eq, empty_state, f32_param, state};
+
+    fn round_trip(toml: bool) {
+        let contents = PresetFile::from_state(&state()).serialize(toml).unwrap();
+        let preset_file = PresetFile::parse(&contents, toml).unwrap();
+        assert_eq!(
+            preset_file.version.as_deref(),
+            Some(env!("CARGO_PKG_VERSION"))
+        );
+
+        let mut imported = empty_state();
+        preset_file.apply_to_state(&mut imported);
+        assert_params_eq(&imported, &state());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        round_trip(false);
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        round_trip(true);
+    }
+
+    #[test]
+    fn missing_fields_are_left_unchanged() {
+        let preset_file = PresetFile::parse(r#"{"buckets": [{"delay": 1.25}]}"#, false).unwrap();
+        assert_eq!(preset_file.version, None);
+
+        let mut imported = state();
+        preset_file.apply_to_state(&mut imported);
+        assert_eq!(f32_param(&imported, "delay_1"), Some(1.25));
+
+        let mut expected = state();
+        expected
+            .params
+            .insert(String::from("delay_1"), ParamValue::F32(1.25));
+        assert_params_eq(&imported, &expected);
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let contents = r#"
+            version = "9.0.0"
+            global_gain_db = -3.0
+
+            [[buckets]]
+            delay = 0.75
+            pan = 0.5
+        "#;
+        let preset_file = PresetFile::parse(contents, true).unwrap();
+
+        let mut imported = state();
+        preset_file.apply_to_state(&mut imported);
+        assert_eq!(f32_param(&imported, "delay_1"), Some(0.75));
+        assert_eq!(imported.params.len(), state().params.len());
+    }
+
+    #[test]
+    fn extra_buckets_are_skipped() {
+        let mut preset_file = PresetFile::from_state(&state());
+        preset_file.buckets.push(PresetFileBucket {
+            delay: Some(1.0),
+            ..PresetFileBucket::default()
+        });
+
+        let mut imported = empty_state();
+        preset_file.apply_to_state(&mut imported);
+        assert_params_eq(&imported, &state());
+    }
+
+    #[test]
+    fn importing_old_files_keeps_the_editor_size() {
+        // Migrating a 0.1.0 file resets the editor size, but that must only affect the preset's
+        // own values and not the state it's imported into
+        let mut preset_file = PresetFile::from_state(&state());
+        preset_file.version = Some(String::from("0.1.0"));
+
+        let mut imported = PluginState {
+            fields: state().fields,
+            ..empty_state()
+        };
+        preset_file.apply_to_state(&mut imported);
+        assert_params_eq(&imported, &state());
+        assert_eq!(imported.fields, state().fields);
+    }
+
+    #[test]
+    fn format_follows_the_extension() {
+        assert!(is_toml(Path::new("preset.toml")));
+        assert!(is_toml(Path::new("preset.TOML")));
+        assert!(!is_toml(Path::new("preset.json")));
+        assert!(!is_toml(Path::new("preset")));
+    }
+}
@@ -0,0 +1,291 @@
+//! Factory presets compiled into the plugin and user presets stored as files in a per-user
+//! directory. Presets are applied through the host facing state restore path, so loading a preset
+//! looks the same to the host as restoring a saved session.
+//!
+//! Both kinds of presets go through [`PresetFile`], so they share the exported preset files'
+//! format and migrations.
+
+use nih_plug::prelude::*;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::preset_file::{PresetFile, PresetFileBucket};
+
+/// The extension used for user preset files. These are [`PresetFile`]s stored as JSON.
+const USER_PRESET_EXTENSION: &str = "json";
+
+/// A preset that can be loaded from the preset browser.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresetEntry {
+    pub name: String,
+    pub source: PresetSource,
+}
+
+/// Where a [`PresetEntry`] comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresetSource {
+    /// An index into [`FACTORY_PRESETS`]. These can't be renamed or deleted.
+    Factory(usize),
+    /// A file in the user preset directory.
+    User(PathBuf),
+}
+
+/// A preset that's compiled into the plugin.
+pub struct FactoryPreset {
+    pub name: &'static str,
+    /// One entry per bucket, in order. Buckets past the end of the list keep their current values.
+    pub buckets: &'static [BucketPreset],
+}
+
+/// The values for a single bucket in a [`FactoryPreset`], using the same units as the
+/// parameters' displayed values.
+pub struct BucketPreset {
+    /// In seconds.
+    pub delay: f32,
+    /// In decibels.
+    pub threshold_db: f32,
+    /// In decibels.
+    pub capacity_db: f32,
+    /// In decibels.
+    pub factor_db: f32,
+}
+
+impl BucketPreset {
+    /// A bucket that lets everything through with unity gain after `delay` seconds.
+    const fn open(delay: f32) -> Self {
+        Self {
+            delay,
+            threshold_db: -30.0,
+            capacity_db: 30.0,
+            factor_db: 0.0,
+        }
+    }
+}
+
+pub const FACTORY_PRESETS: &[FactoryPreset] = &[
+    FactoryPreset {
+        name: "Init",
+        buckets: &[BucketPreset::open(0.0), BucketPreset::open(0.0)],
+    },
+    FactoryPreset {
+        name: "Slapback",
+        buckets: &[BucketPreset::open(0.11), BucketPreset::open(0.23)],
+    },
+    FactoryPreset {
+        name: "Loud Echoes Only",
+        buckets: &[
+            BucketPreset {
+                delay: 0.375,
+                threshold_db: -6.0,
+                capacity_db: 30.0,
+                factor_db: 0.0,
+            },
+            BucketPreset {
+                delay: 0.75,
+                threshold_db: -6.0,
+                capacity_db: 30.0,
+                factor_db: -1.0,
+            },
+        ],
+    },
+    FactoryPreset {
+        name: "Quiet Echoes Only",
+        buckets: &[
+            BucketPreset {
+                delay: 0.25,
+                threshold_db: -30.0,
+                capacity_db: -12.0,
+                factor_db: 1.0,
+            },
+            BucketPreset {
+                delay: 0.5,
+                threshold_db: -30.0,
+                capacity_db: -12.0,
+                factor_db: 1.0,
+            },
+        ],
+    },
+];
+
+impl FactoryPreset {
+    /// The preset as a [`PresetFile`]. This doesn't have a version since factory presets always
+    /// match the current layout.
+    fn preset_file(&self) -> PresetFile {
+        PresetFile {
+            version: None,
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| PresetFileBucket {
+                    delay: Some(bucket.delay),
+                    threshold_db: Some(bucket.threshold_db),
+                    capacity_db: Some(bucket.capacity_db),
+                    factor_db: Some(bucket.factor_db),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The directory user presets are stored in, if the platform has a per-user data directory.
+pub fn user_preset_dir() -> Option<PathBuf> {
+    directories::ProjectDirs::from("com", "ActuallyAdequate", "Border Security Plugin")
+        .map(|dirs| dirs.data_dir().join("presets"))
+}
+
+/// All factory presets followed by all user presets sorted by name. Unreadable user preset
+/// directories are treated as empty.
+pub fn list_presets() -> Vec<PresetEntry> {
+    let mut presets: Vec<PresetEntry> = FACTORY_PRESETS
+        .iter()
+        .enumerate()
+        .map(|(index, preset)| PresetEntry {
+            name: preset.name.to_owned(),
+            source: PresetSource::Factory(index),
+        })
+        .collect();
+
+    let mut user_presets: Vec<PresetEntry> = user_preset_dir()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != USER_PRESET_EXTENSION {
+                return None;
+            }
+
+            Some(PresetEntry {
+                name: path.file_stem()?.to_string_lossy().into_owned(),
+                source: PresetSource::User(path),
+            })
+        })
+        .collect();
+    user_presets.sort_by_key(|preset| preset.name.to_lowercase());
+    presets.extend(user_presets);
+
+    presets
+}
+
+/// Apply `preset` on top of `state`, which should be the plugin's current state. Parameters the
+/// preset doesn't contain keep their current values, and persistent fields like the editor's size
+/// are left alone.
+pub fn apply_preset(preset: &PresetEntry, mut state: PluginState) -> io::Result<PluginState> {
+    let preset_file = match &preset.source {
+        PresetSource::Factory(index) => FACTORY_PRESETS
+            .get(*index)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Unknown factory preset"))?
+            .preset_file(),
+        PresetSource::User(path) => PresetFile::read(path)?,
+    };
+    preset_file.apply_to_state(&mut state);
+
+    Ok(state)
+}
+
+/// Save `state` as a user preset called `name`, overwriting any existing preset with that name.
+/// Returns the path of the new preset file.
+pub fn save_user_preset(name: &str, state: &PluginState) -> io::Result<PathBuf> {
+    let path = user_preset_path(name)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // Only the parameters are part of a preset, the editor's size and other persistent fields are
+    // not
+    PresetFile::from_state(state).write(&path)?;
+
+    Ok(path)
+}
+
+/// Rename the user preset stored at `path` to `new_name`. Returns the preset's new path.
+pub fn rename_user_preset(path: &Path, new_name: &str) -> io::Result<PathBuf> {
+    let new_path = user_preset_path(new_name)?;
+    if new_path != path && new_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("A preset called '{new_name}' already exists"),
+        ));
+    }
+
+    fs::rename(path, &new_path)?;
+    Ok(new_path)
+}
+
+/// Delete the user preset stored at `path`.
+pub fn delete_user_preset(path: &Path) -> io::Result<()> {
+    fs::remove_file(path)
+}
+
+/// The file a user preset called `name` is stored in. Characters that can't be used in file names
+/// on every platform are replaced with underscores.
+fn user_preset_path(name: &str) -> io::Result<PathBuf> {
+    let file_name: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if file_name.is_empty() || file_name.starts_with('.') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Preset names can't be empty or start with a period",
+        ));
+    }
+
+    let dir = user_preset_dir().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not find a directory to store user presets in",
+        )
+    })?;
+    Ok(dir.join(format!("{file_name}.{USER_PRESET_EXTENSION}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{empty_state, f32_param};
+
+    #[test]
+    fn factory_presets_set_their_buckets() {
+        for (index, factory_preset) in FACTORY_PRESETS.iter().enumerate() {
+            let preset = PresetEntry {
+                name: factory_preset.name.to_owned(),
+                source: PresetSource::Factory(index),
+            };
+            let state = apply_preset(&preset, empty_state()).unwrap();
+
+            for (bucket, suffix) in factory_preset.buckets.iter().zip(1..) {
+                let expected = [
+                    ("delay", bucket.delay),
+                    ("threshold", util::db_to_gain(bucket.threshold_db)),
+                    ("capacity", util::db_to_gain(bucket.capacity_db)),
+                    ("factor", util::db_to_gain(bucket.factor_db)),
+                ];
+                for (id, expected) in expected {
+                    let id = format!("{id}_{suffix}");
+                    assert_eq!(
+                        f32_param(&state, &id),
+                        Some(expected),
+                        "{id} in '{}'",
+                        factory_preset.name
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_factory_presets_fail_to_load() {
+        let preset = PresetEntry {
+            name: String::from("Missing"),
+            source: PresetSource::Factory(FACTORY_PRESETS.len()),
+        };
+        assert!(apply_preset(&preset, empty_state()).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+//! Spectra of the input, the wet signal, and the output for the editor. The audio thread only pushes
+//! samples through a lock-free ring buffer, and the FFTs are computed on the GUI thread.
+
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use realfft::num_complex::Complex32;
+use realfft::{RealFftPlanner, RealToComplex};
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// The number of samples each spectrum is computed over.
+pub const SPECTRUM_WINDOW_SIZE: usize = 2048;
+/// The number of bins in each spectrum.
+pub const SPECTRUM_BINS: usize = SPECTRUM_WINDOW_SIZE / 2 + 1;
+/// How many frames the ring buffer between the audio thread and the GUI thread can hold. Frames
+/// are dropped when the GUI thread falls this far behind.
+const RING_BUFFER_CAPACITY: usize = SPECTRUM_WINDOW_SIZE * 8;
+/// How quickly falling spectrum values decay for every update, as the fraction of the old value
+/// that's kept.
+const SPECTRUM_DECAY: f32 = 0.8;
+
+/// A single frame of the three signals, each mixed down to mono.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpectrumFrame {
+    pub input: f32,
+    /// The sum of all buckets before it's mixed with the dry signal.
+    pub wet: f32,
+    pub output: f32,
+}
+
+/// The signals the analyzer shows spectra for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpectrumSignal {
+    Input,
+    Wet,
+    Output,
+}
+
+impl SpectrumSignal {
+    pub const ALL: [SpectrumSignal; 3] = [
+        SpectrumSignal::Input,
+        SpectrumSignal::Wet,
+        SpectrumSignal::Output,
+    ];
+
+    fn sample(self, frame: &SpectrumFrame) -> f32 {
+        match self {
+            SpectrumSignal::Input => frame.input,
+            SpectrumSignal::Wet => frame.wet,
+            SpectrumSignal::Output => frame.output,
+        }
+    }
+}
+
+/// The audio thread's end of the analyzer.
+pub struct SpectrumInput {
+    producer: Producer<SpectrumFrame>,
+    sample_rate: Arc<AtomicF32>,
+}
+
+/// The GUI thread's end of the analyzer. This collects the frames sent by [`SpectrumInput`] and
+/// computes the spectra when [`update()`][Self::update()] is called.
+pub struct SpectrumOutput {
+    consumer: Consumer<SpectrumFrame>,
+    sample_rate: Arc<AtomicF32>,
+
+    fft: Arc<dyn RealToComplex<f32>>,
+    /// A Hann window, compensated so a full scale sine wave ends up at 0 dBFS.
+    window: Vec<f32>,
+    /// The last `SPECTRUM_WINDOW_SIZE` samples of each signal. These are ring buffers that are
+    /// written to at `history_pos`.
+    histories: [Vec<f32>; 3],
+    history_pos: usize,
+
+    /// Scratch buffers for the FFT.
+    fft_input: Vec<f32>,
+    fft_output: Vec<Complex32>,
+    fft_scratch: Vec<Complex32>,
+
+    /// The magnitude spectrum of each signal as linear gains.
+    spectra: [Vec<f32>; 3],
+}
+
+impl SpectrumInput {
+    /// Create the audio thread's end of the analyzer together with the GUI thread's end.
+    pub fn new() -> (Self, SpectrumOutput) {
+        let (producer, consumer) = RingBuffer::new(RING_BUFFER_CAPACITY);
+        let sample_rate = Arc::new(AtomicF32::new(44_100.0));
+
+        let fft = RealFftPlanner::new().plan_fft_forward(SPECTRUM_WINDOW_SIZE);
+        let mut window = util::window::hann(SPECTRUM_WINDOW_SIZE);
+        let window_gain: f32 = window.iter().sum::<f32>() / 2.0;
+        for sample in &mut window {
+            *sample /= window_gain;
+        }
+
+        let output = SpectrumOutput {
+            consumer,
+            sample_rate: sample_rate.clone(),
+
+            fft_input: fft.make_input_vec(),
+            fft_output: fft.make_output_vec(),
+            fft_scratch: fft.make_scratch_vec(),
+            fft,
+            window,
+            histories: std::array::from_fn(|_| vec![0.0; SPECTRUM_WINDOW_SIZE]),
+            history_pos: 0,
+
+            spectra: std::array::from_fn(|_| vec![0.0; SPECTRUM_BINS]),
+        };
+
+        (
+            Self {
+                producer,
+                sample_rate,
+            },
+            output,
+        )
+    }
+
+    /// Let the editor know about the current sample rate.
+    pub fn set_sample_rate(&self, sample_rate: f32) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    /// Send a frame to the editor. This never blocks, and the frame is dropped if the editor isn't
+    /// keeping up.
+    pub fn push(&mut self, frame: SpectrumFrame) {
+        let _ = self.producer.push(frame);
+    }
+}
+
+impl SpectrumOutput {
+    /// The sample rate the spectra were computed for.
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+
+    /// Collect all frames the audio thread has sent since the last update and recompute the
+    /// spectra. Falling values decay slowly to keep the display readable.
+    pub fn update(&mut self) {
+        while let Ok(frame) = self.consumer.pop() {
+            for (history, signal) in self.histories.iter_mut().zip(SpectrumSignal::ALL) {
+                history[self.history_pos] = signal.sample(&frame);
+            }
+            self.history_pos = (self.history_pos + 1) % SPECTRUM_WINDOW_SIZE;
+        }
+
+        for (history, spectrum) in self.histories.iter().zip(&mut self.spectra) {
+            // The history is a ring buffer, so it needs to be unrolled in chronological order
+            let (newer, older) = history.split_at(self.history_pos);
+            for ((fft_sample, sample), window) in self
+                .fft_input
+                .iter_mut()
+                .zip(older.iter().chain(newer))
+                .zip(&self.window)
+            {
+                *fft_sample = sample * window;
+            }
+
+            self.fft
+                .process_with_scratch(
+                    &mut self.fft_input,
+                    &mut self.fft_output,
+                    &mut self.fft_scratch,
+                )
+                .unwrap();
+
+            for (value, bin) in spectrum.iter_mut().zip(&self.fft_output) {
+                let magnitude = bin.norm();
+                *value = if magnitude > *value {
+                    magnitude
+                } else {
+                    *value * SPECTRUM_DECAY + magnitude * (1.0 - SPECTRUM_DECAY)
+                };
+            }
+        }
+    }
+
+    /// The magnitude spectrum of a signal as linear gains, with `SPECTRUM_BINS` bins spread evenly
+    /// between 0 Hz and the Nyquist frequency.
+    pub fn spectrum(&self, signal: SpectrumSignal) -> &[f32] {
+        &self.spectra[signal as usize]
+    }
+}
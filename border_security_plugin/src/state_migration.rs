@@ -0,0 +1,202 @@
+//! Upgrades state saved by older versions of the plugin to the current parameter layout. This runs
+//! from [`Plugin::filter_state()`] for sessions and presets restored by the host, and from
+//! [`PresetFile`][crate::preset_file::PresetFile] for user presets and imported preset files.
+
+use nih_plug::prelude::*;
+
+/// A plugin version as `(major, minor, patch)`.
+type Version = (u32, u32, u32);
+
+/// A change to the saved state's layout, like a renamed parameter ID, a parameter with a new range,
+/// or an added bucket.
+struct Migration {
+    /// The first plugin version that uses the new layout. State saved by older versions gets
+    /// migrated.
+    version: Version,
+    /// Rewrites the state from the layout before `version` to the layout from `version`.
+    migrate: fn(&mut PluginState),
+}
+
+/// All migrations sorted by version. When a release changes the state's layout, bump the crate's
+/// version and add a migration for it here. State is upgraded one migration at a time, so each
+/// migration only needs to handle the layout that came directly before it.
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: (0, 2, 0),
+    migrate: reset_editor_size,
+}];
+
+/// Upgrade `state` to the current layout. State saved by the current or a newer version of the
+/// plugin is left alone.
+pub fn migrate(state: &mut PluginState) {
+    apply_migrations(state, MIGRATIONS);
+}
+
+/// Run the migrations from `migrations` that are newer than the version `state` was saved by, in
+/// order.
+fn apply_migrations(state: &mut PluginState, migrations: &[Migration]) {
+    let saved_version = parse_version(&state.version);
+    for migration in migrations
+        .iter()
+        .filter(|migration| saved_version < migration.version)
+    {
+        nih_log!(
+            "Migrating state saved by version '{}' to the layout of version {}.{}.{}",
+            state.version,
+            migration.version.0,
+            migration.version.1,
+            migration.version.2
+        );
+        (migration.migrate)(state);
+    }
+}
+
+/// Parse a version string like `0.2.0` or `0.2.0-beta`. Missing or unparsable components are
+/// treated as zeroes, so state without a version is treated as coming from the oldest version.
+fn parse_version(version: &str) -> Version {
+    let mut components = version
+        .split(['.', '-', '+'])
+        .map(|component| component.parse().unwrap_or(0));
+    let mut next = || components.next().unwrap_or(0);
+
+    (next(), next(), next())
+}
+
+/// 0.2.0: The editor grew from 600x400 to 800x700 to fit the meters, the analyzers, the preset
+/// browser, and the tap timeline. Those alone take up about 370 pixels of height, so a 0.1.0 sized
+/// window cuts off every bucket's controls. 0.1.0 always saved its editor size, even when the
+/// window was never resized, so those sizes are dropped in favor of the new default size.
+fn reset_editor_size(state: &mut PluginState) {
+    state.fields.remove("editor-state");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::f32_param;
+    use nih_plug::wrapper::state::ParamValue;
+
+    /// A session as version 0.1.0 saves it, with 0.1.0's default editor size. This was written by
+    /// hand from 0.1.0's parameter IDs, ranges, and editor size.
+    const STATE_0_1_0: &str = include_str!("../tests/fixtures/state_0_1_0.json");
+
+    fn fixture() -> PluginState {
+        serde_json::from_str(STATE_0_1_0).expect("The fixture should be valid")
+    }
+
+    #[test]
+    fn parse_versions() {
+        assert_eq!(parse_version(""), (0, 0, 0));
+        assert_eq!(parse_version("0.1.0"), (0, 1, 0));
+        assert_eq!(parse_version("0.2.0-beta"), (0, 2, 0));
+        assert_eq!(parse_version("1.0"), (1, 0, 0));
+    }
+
+    #[test]
+    fn fixture_matches_0_1_0() {
+        let state = fixture();
+        assert_eq!(
+            state.fields.get("editor-state").map(String::as_str),
+            Some(r#"{"size":[600,400],"scale_factor":1.0}"#)
+        );
+
+        // 0.1.0's parameter ranges
+        let ranges = [
+            ("delay", 0.0, 2.0),
+            ("threshold", util::db_to_gain(-30.0), util::db_to_gain(30.0)),
+            ("capacity", util::db_to_gain(-30.0), util::db_to_gain(30.0)),
+            ("factor", util::db_to_gain(-1.0), util::db_to_gain(1.0)),
+        ];
+        for suffix in 1..=2 {
+            for (id, min, max) in ranges {
+                let id = format!("{id}_{suffix}");
+                let value = f32_param(&state, &id).unwrap_or_else(|| panic!("{id} is missing"));
+                assert!((min..=max).contains(&value), "{id} is {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn old_state_loses_the_editor_size() {
+        let old_state = fixture();
+        let mut state = fixture();
+        migrate(&mut state);
+
+        assert!(!state.fields.contains_key("editor-state"));
+        // Only the editor size is reset, all parameter values are kept as is
+        assert_eq!(state.params.len(), old_state.params.len());
+        for suffix in 1..=2 {
+            for id in ["delay", "threshold", "capacity", "factor"] {
+                let id = format!("{id}_{suffix}");
+                assert!(f32_param(&old_state, &id).is_some(), "{id} is missing");
+                assert_eq!(f32_param(&state, &id), f32_param(&old_state, &id), "{id}");
+            }
+        }
+    }
+
+    #[test]
+    fn current_and_newer_state_is_unchanged() {
+        for version in [env!("CARGO_PKG_VERSION"), "0.2.1", "1.0"] {
+            let mut expected = fixture();
+            expected.version = version.to_owned();
+            let mut state = expected.clone();
+            migrate(&mut state);
+
+            assert_eq!(
+                serde_json::to_value(&state).unwrap(),
+                serde_json::to_value(&expected).unwrap(),
+                "{version}"
+            );
+        }
+    }
+
+    /// 0.3.0 in the test table: `factor_1` is renamed to `gain_1`.
+    fn rename_factor(state: &mut PluginState) {
+        if let Some(value) = state.params.remove("factor_1") {
+            state.params.insert(String::from("gain_1"), value);
+        }
+    }
+
+    /// 0.4.0 in the test table: `gain_1` is stored in a range that's twice as large. This only
+    /// finds the value if the rename from 0.3.0 ran first.
+    fn rescale_gain(state: &mut PluginState) {
+        if let Some(ParamValue::F32(value)) = state.params.get_mut("gain_1") {
+            *value *= 2.0;
+        }
+    }
+
+    const TEST_MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: (0, 3, 0),
+            migrate: rename_factor,
+        },
+        Migration {
+            version: (0, 4, 0),
+            migrate: rescale_gain,
+        },
+    ];
+
+    #[test]
+    fn migrations_are_chained_in_order() {
+        let factor = f32_param(&fixture(), "factor_1").unwrap();
+
+        // State from before both migrations goes through the rename and then the rescale
+        let mut state = fixture();
+        apply_migrations(&mut state, TEST_MIGRATIONS);
+        assert_eq!(f32_param(&state, "factor_1"), None);
+        assert_eq!(f32_param(&state, "gain_1"), Some(factor * 2.0));
+
+        // State that was already renamed only gets rescaled
+        let mut state = fixture();
+        state.version = String::from("0.3.0");
+        rename_factor(&mut state);
+        apply_migrations(&mut state, TEST_MIGRATIONS);
+        assert_eq!(f32_param(&state, "gain_1"), Some(factor * 2.0));
+
+        // And state from the last version is left alone
+        let mut state = fixture();
+        state.version = String::from("0.4.0");
+        apply_migrations(&mut state, TEST_MIGRATIONS);
+        assert_eq!(f32_param(&state, "factor_1"), Some(factor));
+        assert_eq!(f32_param(&state, "gain_1"), None);
+    }
+}
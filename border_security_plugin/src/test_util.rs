@@ -0,0 +1,63 @@
+//! Plugin states shared by the preset and state migration tests.
+
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::ParamValue;
+use std::collections::BTreeMap;
+
+use crate::BUCKETS;
+
+/// A state for the current version holding a distinct value for every parameter, and an editor
+/// size.
+pub fn state() -> PluginState {
+    let mut params = BTreeMap::new();
+    for suffix in 1..=BUCKETS {
+        let offset = suffix as f32 * 0.1;
+        params.insert(format!("delay_{suffix}"), ParamValue::F32(0.5 + offset));
+        params.insert(format!("threshold_{suffix}"), ParamValue::F32(0.1 + offset));
+        params.insert(format!("capacity_{suffix}"), ParamValue::F32(2.0 + offset));
+        params.insert(format!("factor_{suffix}"), ParamValue::F32(0.7 + offset));
+    }
+
+    PluginState {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        params,
+        fields: BTreeMap::from([(
+            String::from("editor-state"),
+            String::from(r#"{"size":[800,600],"scale_factor":1.0}"#),
+        )]),
+    }
+}
+
+/// A state for the current version without any parameters or fields, to apply presets to.
+pub fn empty_state() -> PluginState {
+    PluginState {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        params: BTreeMap::new(),
+        fields: BTreeMap::new(),
+    }
+}
+
+/// The value of the `f32` parameter `id`, or `None` if the state doesn't have an `f32` parameter
+/// with that ID.
+pub fn f32_param(state: &PluginState, id: &str) -> Option<f32> {
+    match state.params.get(id) {
+        Some(ParamValue::F32(value)) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Assert that both states have the same `f32` parameters with the same values. Values that went
+/// through decibels don't survive a round trip bit for bit, so they only need to be close.
+pub fn assert_params_eq(a: &PluginState, b: &PluginState) {
+    assert_eq!(
+        a.params.keys().collect::<Vec<_>>(),
+        b.params.keys().collect::<Vec<_>>()
+    );
+    for id in a.params.keys() {
+        let (a, b) = match (f32_param(a, id), f32_param(b, id)) {
+            (Some(a), Some(b)) => (a, b),
+            values => panic!("{id} is {values:?}"),
+        };
+        assert!((a - b).abs() <= a.abs() * 1e-5, "{id}: {a} != {b}");
+    }
+}
@@ -0,0 +1,145 @@
+//! A downsampled picture of the delay line that's built on the audio thread and handed to the
+//! editor without locking.
+
+use nih_plug::prelude::*;
+use triple_buffer::TripleBuffer;
+
+use crate::{BUCKETS, MAX_DELAY};
+
+/// The number of min/max columns the delay line's contents are downsampled to. The columns span
+/// the full `MAX_DELAY` seconds of the delay line.
+pub const WAVEFORM_COLUMNS: usize = 512;
+
+/// The editor's end of the waveform's triple buffer.
+pub type WaveformOutput = triple_buffer::Output<WaveformData>;
+
+/// A snapshot of the delay line's contents and the buckets' taps into it.
+#[derive(Clone)]
+pub struct WaveformData {
+    /// The smallest and largest sample of each column, with all channels mixed down to mono. This
+    /// is a ring buffer, use [`column()`][Self::column()] to read it in chronological order.
+    columns: [(f32, f32); WAVEFORM_COLUMNS],
+    /// The index in `columns` of the column that was completed last.
+    newest_column: usize,
+    /// Where and how each bucket reads from the delay line.
+    pub taps: [WaveformTap; BUCKETS],
+}
+
+/// A bucket's tap into the delay line, as used for the last sample of the published block.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WaveformTap {
+    /// How far back the tap reads, with `0.0` being the newest and `1.0` being the oldest sample
+    /// in the delay line.
+    pub position: f32,
+    /// The lower end of the gate's window as a linear gain.
+    pub threshold: f32,
+    /// The upper end of the gate's window as a linear gain.
+    pub capacity: f32,
+}
+
+/// The audio thread's end of the waveform. Frames are accumulated into columns as they're written
+/// to the delay line, and [`publish()`][Self::publish()] hands the current state to the editor.
+/// Nothing in here allocates after construction.
+pub struct WaveformInput {
+    input: triple_buffer::Input<WaveformData>,
+    /// The state that gets copied to the triple buffer on every publish. Columns are completed
+    /// incrementally, so this can't be built in the triple buffer's own input buffer.
+    data: WaveformData,
+
+    /// How many frames make up a single column for the current sample rate.
+    frames_per_column: usize,
+    /// The number of frames accumulated into `column_range` so far.
+    column_frames: usize,
+    /// The minimum and maximum of the column that's currently being accumulated.
+    column_range: (f32, f32),
+}
+
+impl WaveformData {
+    /// The column that was completed `columns_ago` columns ago, where `0` is the newest column.
+    /// Values past the oldest column are wrapped around.
+    pub fn column(&self, columns_ago: usize) -> (f32, f32) {
+        let columns_ago = columns_ago % WAVEFORM_COLUMNS;
+        self.columns[(self.newest_column + WAVEFORM_COLUMNS - columns_ago) % WAVEFORM_COLUMNS]
+    }
+}
+
+impl Default for WaveformData {
+    fn default() -> Self {
+        Self {
+            columns: [(0.0, 0.0); WAVEFORM_COLUMNS],
+            newest_column: 0,
+            taps: [WaveformTap::default(); BUCKETS],
+        }
+    }
+}
+
+impl WaveformInput {
+    /// Create the audio thread's end of the waveform together with the editor's end.
+    pub fn new() -> (Self, WaveformOutput) {
+        let data = WaveformData::default();
+        let (input, output) = TripleBuffer::new(&data).split();
+
+        (
+            Self {
+                input,
+                data,
+
+                frames_per_column: 1,
+                column_frames: 0,
+                column_range: (0.0, 0.0),
+            },
+            output,
+        )
+    }
+
+    /// Match the columns up with a delay line holding `MAX_DELAY` seconds at `sample_rate`. This
+    /// also clears the waveform.
+    pub fn resize(&mut self, sample_rate: f32) {
+        nih_debug_assert!(sample_rate > 0.0);
+
+        let delay_line_frames = (sample_rate * MAX_DELAY as f32).ceil() as usize;
+        self.frames_per_column = (delay_line_frames / WAVEFORM_COLUMNS).max(1);
+        self.clear();
+    }
+
+    /// Reset the waveform to silence. This does not allocate.
+    pub fn clear(&mut self) {
+        self.data.columns.fill((0.0, 0.0));
+        self.data.newest_column = 0;
+        self.column_frames = 0;
+        self.column_range = (0.0, 0.0);
+    }
+
+    /// Add a frame that was just written to the delay line. The channels are mixed down to mono.
+    pub fn push_frame(&mut self, frame: &[f32]) {
+        if frame.is_empty() {
+            return;
+        }
+
+        let sample = frame.iter().sum::<f32>() / frame.len() as f32;
+        if self.column_frames == 0 {
+            self.column_range = (sample, sample);
+        } else {
+            self.column_range.0 = self.column_range.0.min(sample);
+            self.column_range.1 = self.column_range.1.max(sample);
+        }
+
+        self.column_frames += 1;
+        if self.column_frames == self.frames_per_column {
+            self.data.newest_column = (self.data.newest_column + 1) % WAVEFORM_COLUMNS;
+            self.data.columns[self.data.newest_column] = self.column_range;
+            self.column_frames = 0;
+        }
+    }
+
+    /// Update a bucket's tap. The new value is sent to the editor on the next publish.
+    pub fn set_tap(&mut self, bucket: usize, tap: WaveformTap) {
+        self.data.taps[bucket] = tap;
+    }
+
+    /// Send the current waveform and taps to the editor. This never blocks, and the editor only
+    /// ever sees the most recently published state.
+    pub fn publish(&mut self) {
+        self.input.write(self.data.clone());
+    }
+}
@@ -0,0 +1,320 @@
+//! A level meter showing peak and RMS levels with a peak hold and a clip indicator.
+
+use atomic_float::AtomicF32;
+use nih_plug::prelude::{nih_debug_assert, util};
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// The lowest level shown on the meter.
+const MIN_DB: f32 = -60.0;
+/// The highest level shown on the meter. Anything above 0 dBFS also triggers the clip indicator.
+const MAX_DB: f32 = 6.0;
+/// The width of the clip indicator at the end of the meter in logical pixels.
+const CLIP_INDICATOR_WIDTH: f32 = 8.0;
+/// The width of the peak hold line in logical pixels.
+const PEAK_HOLD_WIDTH: f32 = 2.0;
+
+/// How long it takes for the peak level to fall back to 25% of its value after the signal stops.
+const PEAK_DECAY_MS: f64 = 150.0;
+/// The length of the window the RMS level is averaged over.
+const RMS_WINDOW_MS: f64 = 300.0;
+/// How long the peak hold stays in place before it falls back to the peak level.
+const PEAK_HOLD_MS: f64 = 1000.0;
+
+/// The levels shown by a [`LevelMeter`]. These are written by a [`LevelMeasurement`] on the audio
+/// thread and read by the meter on the GUI thread, so sharing them doesn't require any locking or
+/// allocations.
+#[derive(Default)]
+pub struct LevelMeterData {
+    /// The decaying peak level as a linear gain.
+    peak: AtomicF32,
+    /// The highest recent peak level as a linear gain.
+    peak_hold: AtomicF32,
+    /// The RMS level as a linear gain.
+    rms: AtomicF32,
+    /// Whether the signal has reached 0 dBFS since the clip indicator was last reset.
+    clipped: AtomicBool,
+}
+
+impl LevelMeterData {
+    pub fn peak(&self) -> f32 {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    pub fn peak_hold(&self) -> f32 {
+        self.peak_hold.load(Ordering::Relaxed)
+    }
+
+    pub fn rms(&self) -> f32 {
+        self.rms.load(Ordering::Relaxed)
+    }
+
+    pub fn clipped(&self) -> bool {
+        self.clipped.load(Ordering::Relaxed)
+    }
+
+    /// Turn the clip indicator off again. The [`LevelMeter`] does this when it's clicked on.
+    pub fn reset_clip(&self) {
+        self.clipped.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Measures the levels for a [`LevelMeterData`] on the audio thread. Frames are added with
+/// [`add_frame()`][Self::add_frame()], and the levels are only written to the shared data once per
+/// block when [`publish()`][Self::publish()] is called. Nothing in here allocates.
+pub struct LevelMeasurement {
+    data: Arc<LevelMeterData>,
+
+    /// The per sample weights for the current sample rate.
+    peak_decay_weight: f32,
+    rms_weight: f32,
+    /// How many samples the peak hold stays in place.
+    peak_hold_samples: usize,
+
+    /// The number of samples until the peak hold starts falling.
+    peak_hold_remaining: usize,
+    /// The running mean square that's used to compute the RMS level.
+    mean_square: f32,
+
+    /// The measurements of the block that's currently being added.
+    block_frames: usize,
+    block_peak: f32,
+    block_sum_squares: f32,
+    block_samples: usize,
+    block_clipped: bool,
+}
+
+impl LevelMeasurement {
+    /// Create a measurement that writes to `data`. [`set_sample_rate()`][Self::set_sample_rate()]
+    /// needs to be called before the levels decay correctly.
+    pub fn new(data: Arc<LevelMeterData>) -> Self {
+        Self {
+            data,
+
+            peak_decay_weight: 0.0,
+            rms_weight: 0.0,
+            peak_hold_samples: 0,
+
+            peak_hold_remaining: 0,
+            mean_square: 0.0,
+
+            block_frames: 0,
+            block_peak: 0.0,
+            block_sum_squares: 0.0,
+            block_samples: 0,
+            block_clipped: false,
+        }
+    }
+
+    /// Update the decay and hold times for `sample_rate`.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        nih_debug_assert!(sample_rate > 0.0);
+
+        self.peak_decay_weight = peak_decay_weight(sample_rate, PEAK_DECAY_MS);
+        let sample_rate = sample_rate as f64;
+        self.rms_weight = (-1.0 / (sample_rate * RMS_WINDOW_MS / 1000.0)).exp() as f32;
+        self.peak_hold_samples = (sample_rate * PEAK_HOLD_MS / 1000.0) as usize;
+    }
+
+    /// Reset the measured levels to silence. This keeps the clip indicator as is.
+    pub fn reset(&mut self) {
+        self.peak_hold_remaining = 0;
+        self.mean_square = 0.0;
+        self.block_frames = 0;
+        self.block_peak = 0.0;
+        self.block_sum_squares = 0.0;
+        self.block_samples = 0;
+        self.block_clipped = false;
+
+        self.data.peak.store(0.0, Ordering::Relaxed);
+        self.data.peak_hold.store(0.0, Ordering::Relaxed);
+        self.data.rms.store(0.0, Ordering::Relaxed);
+    }
+
+    /// Add a frame containing one sample per channel. The meter shows the loudest channel's peak
+    /// and the RMS level across all channels.
+    pub fn add_frame(&mut self, frame: impl IntoIterator<Item = f32>) {
+        for sample in frame {
+            let amplitude = sample.abs();
+            self.block_peak = self.block_peak.max(amplitude);
+            self.block_sum_squares += sample * sample;
+            self.block_samples += 1;
+            self.block_clipped |= amplitude >= 1.0;
+        }
+
+        self.block_frames += 1;
+    }
+
+    /// Write the levels for the frames added since the last call to the shared data.
+    pub fn publish(&mut self) {
+        if self.block_frames == 0 {
+            return;
+        }
+
+        let num_frames = self.block_frames as i32;
+        let block_mean_square = self.block_sum_squares / self.block_samples.max(1) as f32;
+
+        // Rising peaks are shown immediately, but falling peaks decay slowly enough to be readable
+        let new_peak = decay_peak(
+            self.data.peak(),
+            self.block_peak,
+            self.peak_decay_weight,
+            self.block_frames,
+        );
+        self.data.peak.store(new_peak, Ordering::Relaxed);
+
+        let rms_weight = self.rms_weight.powi(num_frames);
+        self.mean_square = self.mean_square * rms_weight + block_mean_square * (1.0 - rms_weight);
+        self.data
+            .rms
+            .store(self.mean_square.sqrt(), Ordering::Relaxed);
+
+        let current_peak_hold = self.data.peak_hold();
+        if self.block_peak >= current_peak_hold {
+            self.data
+                .peak_hold
+                .store(self.block_peak, Ordering::Relaxed);
+            self.peak_hold_remaining = self.peak_hold_samples;
+        } else if self.peak_hold_remaining > self.block_frames {
+            self.peak_hold_remaining -= self.block_frames;
+        } else {
+            self.peak_hold_remaining = 0;
+            self.data.peak_hold.store(new_peak, Ordering::Relaxed);
+        }
+
+        // The clip indicator latches until the user resets it
+        if self.block_clipped {
+            self.data.clipped.store(true, Ordering::Relaxed);
+        }
+
+        self.block_frames = 0;
+        self.block_peak = 0.0;
+        self.block_sum_squares = 0.0;
+        self.block_samples = 0;
+        self.block_clipped = false;
+    }
+}
+
+/// The per sample weight for [`decay_peak()`] at `sample_rate`, so a peak falls back to 25% of its
+/// value after `decay_ms` milliseconds regardless of the sample rate.
+pub fn peak_decay_weight(sample_rate: f32, decay_ms: f64) -> f32 {
+    0.25f64.powf((sample_rate as f64 * decay_ms / 1000.0).recip()) as f32
+}
+
+/// The new value of a decaying peak that was `current_peak`, after measuring a peak of `peak` over
+/// `num_samples` samples. Rising peaks are taken over immediately and falling peaks decay using
+/// `decay_weight`, which comes from [`peak_decay_weight()`].
+pub fn decay_peak(current_peak: f32, peak: f32, decay_weight: f32, num_samples: usize) -> f32 {
+    if peak > current_peak {
+        peak
+    } else {
+        let block_decay_weight = decay_weight.powi(num_samples as i32);
+        current_peak * block_decay_weight + peak * (1.0 - block_decay_weight)
+    }
+}
+
+/// A horizontal level meter on a decibel scale. The RMS level is drawn as a solid bar using the
+/// font color, with the peak level as a translucent bar behind it and the peak hold as a line. The
+/// clip indicator at the end of the meter lights up red once the signal reaches 0 dBFS, and stays
+/// lit until the meter is clicked on.
+pub struct LevelMeter {
+    data: Arc<LevelMeterData>,
+}
+
+impl LevelMeter {
+    /// Creates a new [`LevelMeter`] showing the levels in `data`.
+    pub fn new<L>(cx: &mut Context, data: L) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<LevelMeterData>>,
+    {
+        Self { data: data.get(cx) }.build(cx, |_| {})
+    }
+}
+
+/// Where a linear gain falls on a meter with a decibel scale ranging from `min_db` to `max_db`,
+/// between `0.0` and `1.0`.
+pub fn gain_to_t(gain: f32, min_db: f32, max_db: f32) -> f32 {
+    ((util::gain_to_db(gain) - min_db) / (max_db - min_db)).clamp(0.0, 1.0)
+}
+
+impl View for LevelMeter {
+    fn element(&self) -> Option<&'static str> {
+        Some("level-meter")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| {
+            if let WindowEvent::MouseDown(MouseButton::Left) = window_event {
+                self.data.reset_clip();
+                cx.needs_redraw();
+
+                meta.consume();
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let dpi_scale = cx.scale_factor();
+        let opacity = cx.opacity();
+        let mut background_color: vg::Color = cx.background_color().into();
+        background_color.set_alphaf(background_color.a * opacity);
+        let mut rms_color: vg::Color = cx.font_color().into();
+        rms_color.set_alphaf(rms_color.a * opacity);
+        let mut peak_color = rms_color;
+        peak_color.set_alphaf(rms_color.a * 0.4);
+        let mut clip_color = vg::Color::rgb(230, 50, 40);
+        clip_color.set_alphaf(if self.data.clipped() {
+            opacity
+        } else {
+            0.2 * opacity
+        });
+
+        let mut path = vg::Path::new();
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&mut path, &vg::Paint::color(background_color));
+
+        let clip_indicator_width = CLIP_INDICATOR_WIDTH * dpi_scale;
+        let meter_width = (bounds.w - clip_indicator_width - dpi_scale).max(0.0);
+
+        let mut path = vg::Path::new();
+        path.rect(
+            bounds.x,
+            bounds.y,
+            gain_to_t(self.data.peak(), MIN_DB, MAX_DB) * meter_width,
+            bounds.h,
+        );
+        canvas.fill_path(&mut path, &vg::Paint::color(peak_color));
+
+        let mut path = vg::Path::new();
+        path.rect(
+            bounds.x,
+            bounds.y,
+            gain_to_t(self.data.rms(), MIN_DB, MAX_DB) * meter_width,
+            bounds.h,
+        );
+        canvas.fill_path(&mut path, &vg::Paint::color(rms_color));
+
+        let peak_hold_width = PEAK_HOLD_WIDTH * dpi_scale;
+        let peak_hold_x = bounds.x
+            + gain_to_t(self.data.peak_hold(), MIN_DB, MAX_DB) * (meter_width - peak_hold_width);
+        let mut path = vg::Path::new();
+        path.rect(peak_hold_x, bounds.y, peak_hold_width, bounds.h);
+        canvas.fill_path(&mut path, &vg::Paint::color(rms_color));
+
+        let mut path = vg::Path::new();
+        path.rect(
+            bounds.x + bounds.w - clip_indicator_width,
+            bounds.y,
+            clip_indicator_width,
+            bounds.h,
+        );
+        canvas.fill_path(&mut path, &vg::Paint::color(clip_color));
+    }
+}
@@ -1 +1,7 @@
+pub mod level_meter;
+pub mod param_knob;
+pub mod range_slider;
+pub mod snap_grid;
+pub mod time_format;
 pub mod time_slider;
+pub mod value_formatter;
@@ -0,0 +1,538 @@
+//! A rotary knob that integrates with NIH-plug's [`Param`] types.
+
+use nih_plug::prelude::{Param, ParamPtr};
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+
+use nih_plug_vizia::widgets::param_base::ParamWidgetBase;
+use nih_plug_vizia::widgets::util::ModifiersExt;
+
+use crate::time_slider::GRANULAR_DRAG_MULTIPLIER;
+use crate::value_formatter::{DefaultFormatter, FormatterParam, SharedFormatter, ValueFormatter};
+
+/// The number of logical pixels the mouse needs to be dragged vertically to sweep the knob over
+/// its entire range.
+const DRAG_DISTANCE: f32 = 200.0;
+/// The angle in radians where the knob's arc starts, measured clockwise from the right. The arc
+/// leaves a gap at the bottom of the knob.
+const ARC_START_ANGLE: f32 = 0.75 * std::f32::consts::PI;
+/// The angle in radians the arc sweeps over from the parameter's minimum to its maximum value.
+const ARC_SWEEP_ANGLE: f32 = 1.5 * std::f32::consts::PI;
+
+/// A rotary knob that integrates with NIH-plug's [`Param`] types. Dragging vertically changes the
+/// value, and the knob otherwise behaves the same as the
+/// [`TimeSlider`][crate::time_slider::TimeSlider]: Shift+drag for granular dragging, scrolling
+/// for steps, double click to reset, and Alt+Click for text entry. Use the
+/// [`set_style()`][ParamKnobExt::set_style()] method to change how the arc gets filled.
+#[derive(Lens)]
+pub struct ParamKnob {
+    param_base: ParamWidgetBase,
+    /// Needed to give the [`ValueFormatter`] access to the parameter when parsing the text input.
+    param_ptr: ParamPtr,
+
+    /// Will be set to `true` when the field gets Alt+Click'ed which will replace the label with a
+    /// text box.
+    text_input_active: bool,
+    /// Will be set to `true` when the text entered in the text box could not be parsed.
+    text_input_invalid: bool,
+    /// Will be set while dragging the knob. Resetting the parameter or entering a text value should
+    /// not initiate a drag.
+    drag_status: Option<KnobDragStatus>,
+
+    // These fields are set through modifiers:
+    /// Whether or not to listen to scroll events for changing the parameter's value in steps.
+    use_scroll_wheel: bool,
+    /// The number of (fractional) scrolled lines that have not yet been turned into parameter
+    /// change events. This is needed to support trackpads with smooth scrolling.
+    scrolled_lines: f32,
+    /// How the arc should be filled.
+    style: ParamKnobStyle,
+    /// Converts the parameter's value to the displayed text and parses the text input.
+    formatter: SharedFormatter,
+}
+
+/// How the [`ParamKnob`] should fill its arc. Set this using [`ParamKnobExt::set_style()`]. These
+/// mirror the continuous styles from [`TimeSliderStyle`][crate::time_slider::TimeSliderStyle].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum ParamKnobStyle {
+    /// Fill the arc from the default value for continuous parameters with a default value at
+    /// around half of its range, making the knob bipolar. Fill the arc from the start otherwise.
+    Centered,
+    /// Always fill the arc starting from the parameter's minimum value.
+    FromLeft,
+}
+
+enum ParamKnobEvent {
+    /// Text input has been cancelled without submitting a new value.
+    CancelTextInput,
+    /// A new value has been sent by the text input dialog after pressing Enter.
+    TextInput(String),
+}
+
+/// Dragging is relative to where the drag started. The starting point gets moved when Shift is
+/// pressed or released so the knob doesn't jump when switching to or from granular dragging.
+// TODO: Vizia's lens derive macro requires this to be marked as pub
+#[derive(Debug, Clone, Copy)]
+pub struct KnobDragStatus {
+    /// The mouse's Y-coordinate when the drag was started.
+    pub starting_y_coordinate: f32,
+    /// The normalized value when the drag was started.
+    pub starting_value: f32,
+    /// Whether Shift was held down when the drag was started.
+    pub granular: bool,
+}
+
+impl ParamKnob {
+    /// Creates a new [`ParamKnob`] for the given parameter. This takes the same arguments as
+    /// [`TimeSlider::new()`][crate::time_slider::TimeSlider::new()].
+    ///
+    /// See [`ParamKnobExt`] for additional options.
+    pub fn new<L, Params, P, FMap>(
+        cx: &mut Context,
+        params: L,
+        params_to_param: FMap,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        P: Param + 'static,
+        FMap: Fn(&Params) -> &P + Copy + 'static,
+    {
+        // This is the same trick `ParamWidgetBase` uses to get a pointer to the parameter without
+        // any lifetime bounds
+        let param_ptr = params
+            .clone()
+            .map(move |params| params_to_param(params).as_ptr())
+            .get(cx);
+
+        Self {
+            param_base: ParamWidgetBase::new(cx, params.clone(), params_to_param),
+            param_ptr,
+
+            text_input_active: false,
+            text_input_invalid: false,
+            drag_status: None,
+
+            use_scroll_wheel: true,
+            scrolled_lines: 0.0,
+            style: ParamKnobStyle::Centered,
+            formatter: SharedFormatter::new(DefaultFormatter),
+        }
+        .build(
+            cx,
+            ParamWidgetBase::build_view(params, params_to_param, move |cx, param_data| {
+                Binding::new(cx, ParamKnob::formatter, move |cx, formatter| {
+                    let formatter = formatter.get(cx);
+                    let param_data = param_data.clone();
+
+                    Binding::new(cx, ParamKnob::style, move |cx, style| {
+                        let style = style.get(cx);
+
+                        // Needs to be moved into the below closures, and it can't be `Copy`
+                        let param_data = param_data.clone();
+                        let formatter = formatter.clone();
+
+                        let unmodulated_normalized_value_lens =
+                            param_data.make_lens(|param| param.unmodulated_normalized_value());
+                        let display_value_lens = param_data.make_lens(move |param| {
+                            formatter.format(
+                                &FormatterParam::new(param),
+                                param.unmodulated_normalized_value(),
+                            )
+                        });
+
+                        // These follow the same `(start_t, delta)` format as the slider's fill
+                        let fill_start_delta_lens = {
+                            let param_data = param_data.clone();
+                            unmodulated_normalized_value_lens
+                                .clone()
+                                .map(move |current_value| {
+                                    Self::compute_fill_start_delta(
+                                        style,
+                                        param_data.param(),
+                                        *current_value,
+                                    )
+                                })
+                        };
+                        let modulation_start_delta_lens = param_data.make_lens(|param| {
+                            let modulation_start = param.unmodulated_normalized_value();
+
+                            (
+                                modulation_start,
+                                param.modulated_normalized_value() - modulation_start,
+                            )
+                        });
+
+                        KnobArc {
+                            value: unmodulated_normalized_value_lens,
+                            fill_start_delta: fill_start_delta_lens,
+                            modulation_start_delta: modulation_start_delta_lens,
+                        }
+                        .build(cx, |_| {})
+                        .class("arc")
+                        .width(Stretch(1.0))
+                        .height(Stretch(1.0))
+                        .hoverable(false);
+
+                        Binding::new(
+                            cx,
+                            ParamKnob::text_input_active,
+                            move |cx, text_input_active| {
+                                if text_input_active.get(cx) {
+                                    Self::text_input_view(cx, display_value_lens.clone());
+                                } else {
+                                    Label::new(cx, display_value_lens.clone())
+                                        .class("value")
+                                        .child_space(Stretch(1.0))
+                                        .height(Pixels(20.0))
+                                        .width(Stretch(1.0))
+                                        .hoverable(false);
+                                }
+                            },
+                        );
+                    });
+                });
+            }),
+        )
+    }
+
+    /// Create a text input that's shown in place of the value label.
+    fn text_input_view(cx: &mut Context, display_value_lens: impl Lens<Target = String>) {
+        Textbox::new(cx, display_value_lens)
+            .class("value-entry")
+            .toggle_class("value-entry--invalid", ParamKnob::text_input_invalid)
+            .border_color(ParamKnob::text_input_invalid.map(|invalid| {
+                if *invalid {
+                    Color::rgb(220, 50, 47)
+                } else {
+                    Color::transparent()
+                }
+            }))
+            .border_width(Pixels(1.0))
+            .on_submit(|cx, string, success| {
+                if success {
+                    cx.emit(ParamKnobEvent::TextInput(string))
+                } else {
+                    cx.emit(ParamKnobEvent::CancelTextInput);
+                }
+            })
+            .on_build(|cx| {
+                cx.emit(TextEvent::StartEdit);
+                cx.emit(TextEvent::SelectAll);
+            })
+            .class("align_center")
+            .child_top(Stretch(1.0))
+            .child_bottom(Stretch(1.0))
+            .height(Pixels(20.0))
+            .width(Stretch(1.0));
+    }
+
+    /// Calculate the start position and the signed length of the knob's filled arc, both as
+    /// normalized values.
+    fn compute_fill_start_delta<P: Param>(
+        style: ParamKnobStyle,
+        param: &P,
+        current_value: f32,
+    ) -> (f32, f32) {
+        let default_value = param.default_normalized_value();
+        let draw_fill_from_default = style == ParamKnobStyle::Centered
+            && param.step_count().is_none()
+            && (0.45..=0.55).contains(&default_value);
+
+        if draw_fill_from_default {
+            (default_value, current_value - default_value)
+        } else {
+            (0.0, current_value)
+        }
+    }
+
+    /// Reset the parameter to its default value, wrapped in a parameter automation gesture.
+    fn reset_to_default(&self, cx: &mut EventContext) {
+        self.param_base.begin_set_parameter(cx);
+        self.param_base
+            .set_normalized_value(cx, self.param_base.default_normalized_value());
+        self.param_base.end_set_parameter(cx);
+    }
+}
+
+impl View for ParamKnob {
+    fn element(&self) -> Option<&'static str> {
+        Some("param-knob")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|param_knob_event, meta| match param_knob_event {
+            ParamKnobEvent::CancelTextInput => {
+                self.text_input_active = false;
+                self.text_input_invalid = false;
+                cx.set_active(false);
+
+                meta.consume();
+            }
+            ParamKnobEvent::TextInput(string) => {
+                // SAFETY: The parameter outlives the editor, `ParamWidgetBase` relies on the same
+                //         guarantee
+                let param = unsafe { FormatterParam::from_ptr(self.param_ptr) };
+                match self.formatter.parse(&param, string) {
+                    Some(normalized_value) => {
+                        self.param_base.begin_set_parameter(cx);
+                        self.param_base.set_normalized_value(cx, normalized_value);
+                        self.param_base.end_set_parameter(cx);
+
+                        self.text_input_active = false;
+                        self.text_input_invalid = false;
+                    }
+                    // Keep the text box open so the mistake can be corrected
+                    None => self.text_input_invalid = true,
+                }
+
+                meta.consume();
+            }
+        });
+
+        event.map(|window_event, meta| match window_event {
+            // See `TimeSlider` for why triple clicks are treated as regular clicks
+            WindowEvent::MouseDown(MouseButton::Left)
+            | WindowEvent::MouseTripleClick(MouseButton::Left) => {
+                if cx.modifiers.alt() {
+                    // ALt+Click brings up a text entry dialog
+                    self.text_input_active = true;
+                    cx.set_active(true);
+                } else if cx.modifiers.command() {
+                    self.reset_to_default(cx);
+                } else {
+                    cx.capture();
+                    // NOTE: Otherwise we don't get key up events
+                    cx.focus();
+                    cx.set_active(true);
+
+                    self.param_base.begin_set_parameter(cx);
+                    self.drag_status = Some(KnobDragStatus {
+                        starting_y_coordinate: cx.mouse.cursory,
+                        starting_value: self.param_base.unmodulated_normalized_value(),
+                        granular: cx.modifiers.shift(),
+                    });
+                }
+
+                meta.consume();
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left)
+            | WindowEvent::MouseDown(MouseButton::Right)
+            | WindowEvent::MouseDoubleClick(MouseButton::Right)
+            | WindowEvent::MouseTripleClick(MouseButton::Right) => {
+                // Double click and right clicks should reset the parameter instead of initiating a
+                // drag operation
+                self.reset_to_default(cx);
+
+                meta.consume();
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.drag_status.take().is_some() {
+                    cx.release();
+                    cx.set_active(false);
+
+                    self.param_base.end_set_parameter(cx);
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(_x, y) => {
+                if let Some(drag_status) = &mut self.drag_status {
+                    // Switching between regular and granular dragging restarts the drag from the
+                    // current position so the value doesn't jump
+                    let granular = cx.modifiers.shift();
+                    if granular != drag_status.granular {
+                        *drag_status = KnobDragStatus {
+                            starting_y_coordinate: *y,
+                            starting_value: self.param_base.unmodulated_normalized_value(),
+                            granular,
+                        };
+                    }
+
+                    // Dragging up increases the value. This should be compensated for the DPI
+                    // scale so it remains consistent.
+                    let mut delta = (drag_status.starting_y_coordinate - *y)
+                        / (DRAG_DISTANCE * cx.style.dpi_factor as f32);
+                    if granular {
+                        delta *= GRANULAR_DRAG_MULTIPLIER;
+                    }
+
+                    let normalized_value = (drag_status.starting_value + delta).clamp(0.0, 1.0);
+                    self.param_base.set_normalized_value(cx, normalized_value);
+                }
+            }
+            WindowEvent::MouseScroll(_scroll_x, scroll_y) if self.use_scroll_wheel => {
+                // With a regular scroll wheel `scroll_y` will only ever be -1 or 1, but with smooth
+                // scrolling trackpads being a thing `scroll_y` could be anything.
+                self.scrolled_lines += scroll_y;
+
+                if self.scrolled_lines.abs() >= 1.0 {
+                    let use_finer_steps = cx.modifiers.shift();
+                    let drag_active = self.drag_status.is_some();
+
+                    // Scrolling while dragging needs to be taken into account here
+                    if !drag_active {
+                        self.param_base.begin_set_parameter(cx);
+                    }
+
+                    let mut current_value = self.param_base.unmodulated_normalized_value();
+
+                    while self.scrolled_lines >= 1.0 {
+                        current_value = self
+                            .param_base
+                            .next_normalized_step(current_value, use_finer_steps);
+                        self.param_base.set_normalized_value(cx, current_value);
+                        self.scrolled_lines -= 1.0;
+                    }
+
+                    while self.scrolled_lines <= -1.0 {
+                        current_value = self
+                            .param_base
+                            .previous_normalized_step(current_value, use_finer_steps);
+                        self.param_base.set_normalized_value(cx, current_value);
+                        self.scrolled_lines += 1.0;
+                    }
+
+                    if !drag_active {
+                        self.param_base.end_set_parameter(cx);
+                    }
+                }
+
+                meta.consume();
+            }
+            _ => {}
+        });
+    }
+}
+
+/// The arc part of the knob. The unfilled track uses the border color, the filled part and the
+/// pointer use the font color, and the modulation arc uses a translucent version of the font
+/// color.
+struct KnobArc<V, F, M>
+where
+    V: Lens<Target = f32>,
+    F: Lens<Target = (f32, f32)>,
+    M: Lens<Target = (f32, f32)>,
+{
+    value: V,
+    fill_start_delta: F,
+    modulation_start_delta: M,
+}
+
+impl<V, F, M> View for KnobArc<V, F, M>
+where
+    V: Lens<Target = f32>,
+    F: Lens<Target = (f32, f32)>,
+    M: Lens<Target = (f32, f32)>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("knob-arc")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let dpi_scale = cx.scale_factor();
+        let opacity = cx.opacity();
+        let mut track_color: vg::Color = cx.border_color().into();
+        track_color.set_alphaf(track_color.a * opacity);
+        let mut fill_color: vg::Color = cx.font_color().into();
+        fill_color.set_alphaf(fill_color.a * opacity);
+        let mut modulation_color = fill_color;
+        modulation_color.set_alphaf(fill_color.a * 0.5);
+
+        let line_width = 3.0 * dpi_scale;
+        let center_x = bounds.x + bounds.w / 2.0;
+        let center_y = bounds.y + bounds.h / 2.0;
+        let radius = (bounds.w.min(bounds.h) / 2.0) - line_width;
+        if radius <= 0.0 {
+            return;
+        }
+
+        let stroke_arc = |canvas: &mut Canvas, start_t: f32, end_t: f32, color: vg::Color| {
+            let (start_t, end_t) = (start_t.min(end_t), start_t.max(end_t));
+            if end_t - start_t < 1e-3 {
+                return;
+            }
+
+            let mut path = vg::Path::new();
+            path.arc(
+                center_x,
+                center_y,
+                radius,
+                ARC_START_ANGLE + start_t * ARC_SWEEP_ANGLE,
+                ARC_START_ANGLE + end_t * ARC_SWEEP_ANGLE,
+                vg::Solidity::Hole,
+            );
+
+            let mut paint = vg::Paint::color(color);
+            paint.set_line_width(line_width);
+            paint.set_line_cap(vg::LineCap::Round);
+            canvas.stroke_path(&mut path, &paint);
+        };
+
+        stroke_arc(canvas, 0.0, 1.0, track_color);
+
+        let (fill_start, fill_delta) = self.fill_start_delta.get(cx);
+        stroke_arc(canvas, fill_start, fill_start + fill_delta, fill_color);
+
+        // This only shows up for CLAP plugins in hosts that support parameter modulation
+        let (modulation_start, modulation_delta) = self.modulation_start_delta.get(cx);
+        stroke_arc(
+            canvas,
+            modulation_start,
+            modulation_start + modulation_delta,
+            modulation_color,
+        );
+
+        // The pointer from the center towards the current unmodulated value
+        let angle = ARC_START_ANGLE + self.value.get(cx) * ARC_SWEEP_ANGLE;
+        let mut path = vg::Path::new();
+        path.move_to(
+            center_x + angle.cos() * radius * 0.3,
+            center_y + angle.sin() * radius * 0.3,
+        );
+        path.line_to(
+            center_x + angle.cos() * radius,
+            center_y + angle.sin() * radius,
+        );
+
+        let mut paint = vg::Paint::color(fill_color);
+        paint.set_line_width(line_width);
+        paint.set_line_cap(vg::LineCap::Round);
+        canvas.stroke_path(&mut path, &paint);
+    }
+}
+
+/// Extension methods for [`ParamKnob`] handles.
+pub trait ParamKnobExt {
+    /// Don't respond to scroll wheel events. Useful when this knob is used as part of a scrolling
+    /// view.
+    fn disable_scroll_wheel(self) -> Self;
+
+    /// Change how the [`ParamKnob`] fills its arc.
+    fn set_style(self, style: ParamKnobStyle) -> Self;
+
+    /// Use a custom [`ValueFormatter`] for displaying the parameter's value and parsing text input.
+    /// By default the parameter's own formatting is used.
+    fn with_formatter(self, formatter: impl ValueFormatter) -> Self;
+}
+
+impl ParamKnobExt for Handle<'_, ParamKnob> {
+    fn disable_scroll_wheel(self) -> Self {
+        self.modify(|param_knob: &mut ParamKnob| param_knob.use_scroll_wheel = false)
+    }
+
+    fn set_style(self, style: ParamKnobStyle) -> Self {
+        self.modify(|param_knob: &mut ParamKnob| param_knob.style = style)
+    }
+
+    fn with_formatter(self, formatter: impl ValueFormatter) -> Self {
+        self.modify(|param_knob: &mut ParamKnob| {
+            param_knob.formatter = SharedFormatter::new(formatter)
+        })
+    }
+}
@@ -0,0 +1,316 @@
+//! A slider that binds two parameters to the low and high ends of a single range.
+
+use nih_plug::prelude::Param;
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+
+use nih_plug_vizia::widgets::param_base::ParamWidgetBase;
+use nih_plug_vizia::widgets::util::remap_current_entity_x_coordinate;
+use nih_plug_vizia::widgets::util::remap_current_entity_x_t;
+
+/// How close to a handle, in logical pixels, a click needs to be to grab that handle instead of
+/// the window between the handles.
+const HANDLE_GRAB_DISTANCE: f32 = 6.0;
+/// The width of the handles in logical pixels.
+const HANDLE_WIDTH: f32 = 3.0;
+
+/// A slider with a low and a high handle on a single track, each bound to its own parameter. The
+/// region between the handles is highlighted. Dragging a handle moves that end of the range, and
+/// dragging the region between the handles moves the whole window while keeping its width. The
+/// handles can't be dragged past each other, so the low parameter stays at or below the high
+/// parameter when edited through this widget. Hosts and automation can still make them cross, so
+/// plugins should handle that case as well.
+///
+/// Both parameters are compared using their normalized values, so they should have the same range.
+#[derive(Lens)]
+pub struct RangeSlider {
+    low: ParamWidgetBase,
+    high: ParamWidgetBase,
+
+    /// Set while dragging, contains which part of the slider is being dragged.
+    drag_status: Option<RangeDragStatus>,
+}
+
+/// Which part of a [`RangeSlider`] is being dragged.
+// TODO: Vizia's lens derive macro requires this to be marked as pub
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangeDragStatus {
+    /// Only the low handle is being dragged.
+    Low,
+    /// Only the high handle is being dragged.
+    High,
+    /// The window between the handles is being dragged.
+    Window {
+        /// The normalized mouse position when the drag was started.
+        starting_t: f32,
+        /// The low and high normalized values when the drag was started.
+        starting_values: (f32, f32),
+    },
+}
+
+impl RangeSlider {
+    /// Creates a new [`RangeSlider`] for the given low and high parameters. This works the same
+    /// way as [`TimeSlider::new()`][crate::time_slider::TimeSlider::new()], but with a projection
+    /// function for each of the two parameters.
+    pub fn new<L, Params, PLow, PHigh, FLow, FHigh>(
+        cx: &mut Context,
+        params: L,
+        params_to_low: FLow,
+        params_to_high: FHigh,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        PLow: Param + 'static,
+        PHigh: Param + 'static,
+        FLow: Fn(&Params) -> &PLow + Copy + 'static,
+        FHigh: Fn(&Params) -> &PHigh + Copy + 'static,
+    {
+        Self {
+            low: ParamWidgetBase::new(cx, params.clone(), params_to_low),
+            high: ParamWidgetBase::new(cx, params.clone(), params_to_high),
+
+            drag_status: None,
+        }
+        .build(cx, move |cx| {
+            // Can't use `.to_string()` here as that would include the modulation
+            let low_value_lens = params
+                .clone()
+                .map(move |params| params_to_low(params).unmodulated_normalized_value());
+            let high_value_lens = params
+                .clone()
+                .map(move |params| params_to_high(params).unmodulated_normalized_value());
+            let low_display_lens = params.clone().map(move |params| {
+                let param = params_to_low(params);
+                param.normalized_value_to_string(param.unmodulated_normalized_value(), true)
+            });
+            let high_display_lens = params.map(move |params| {
+                let param = params_to_high(params);
+                param.normalized_value_to_string(param.unmodulated_normalized_value(), true)
+            });
+
+            ZStack::new(cx, move |cx| {
+                RangeTrack {
+                    low: low_value_lens,
+                    high: high_value_lens,
+                }
+                .build(cx, |_| {})
+                .class("track")
+                .width(Stretch(1.0))
+                .height(Stretch(1.0))
+                .hoverable(false);
+
+                HStack::new(cx, move |cx| {
+                    Label::new(cx, low_display_lens)
+                        .class("value")
+                        .class("value--low")
+                        .child_left(Pixels(6.0))
+                        .child_right(Stretch(1.0))
+                        .child_top(Stretch(1.0))
+                        .child_bottom(Stretch(1.0))
+                        .height(Stretch(1.0))
+                        .width(Stretch(1.0))
+                        .hoverable(false);
+                    Label::new(cx, high_display_lens)
+                        .class("value")
+                        .class("value--high")
+                        .child_left(Stretch(1.0))
+                        .child_right(Pixels(6.0))
+                        .child_top(Stretch(1.0))
+                        .child_bottom(Stretch(1.0))
+                        .height(Stretch(1.0))
+                        .width(Stretch(1.0))
+                        .hoverable(false);
+                })
+                .hoverable(false);
+            })
+            .hoverable(false);
+        })
+    }
+
+    /// Pick what should be dragged when clicking at X-coordinate `x`. Clicks close to or outside of
+    /// a handle grab that handle, and clicks between the handles grab the window.
+    fn drag_target(&self, cx: &EventContext, x: f32) -> RangeDragStatus {
+        let low = self.low.unmodulated_normalized_value();
+        let high = self.high.unmodulated_normalized_value();
+        let t = remap_current_entity_x_coordinate(cx, x);
+
+        // The grab distance should be compensated for the DPI scale so it remains consistent
+        let grab_distance = HANDLE_GRAB_DISTANCE * cx.style.dpi_factor as f32;
+        let low_distance = (x - remap_current_entity_x_t(cx, low)).abs();
+        let high_distance = (x - remap_current_entity_x_t(cx, high)).abs();
+        if t <= low || (low_distance <= grab_distance && low_distance <= high_distance) {
+            RangeDragStatus::Low
+        } else if t >= high || high_distance <= grab_distance {
+            RangeDragStatus::High
+        } else {
+            RangeDragStatus::Window {
+                starting_t: t,
+                starting_values: (low, high),
+            }
+        }
+    }
+
+    /// Update the dragged parameters for the mouse at normalized position `t`. The handles can't be
+    /// moved past each other, and the window can't be moved outside of the track.
+    fn drag_to(&self, cx: &mut EventContext, drag_status: RangeDragStatus, t: f32) {
+        let t = t.clamp(0.0, 1.0);
+        match drag_status {
+            RangeDragStatus::Low => {
+                let high = self.high.unmodulated_normalized_value();
+                self.low.set_normalized_value(cx, t.min(high));
+            }
+            RangeDragStatus::High => {
+                let low = self.low.unmodulated_normalized_value();
+                self.high.set_normalized_value(cx, t.max(low));
+            }
+            RangeDragStatus::Window {
+                starting_t,
+                starting_values: (low, high),
+            } => {
+                let delta = (t - starting_t).clamp(-low, 1.0 - high);
+                self.low.set_normalized_value(cx, low + delta);
+                self.high.set_normalized_value(cx, high + delta);
+            }
+        }
+    }
+
+    fn begin_set_parameters(&self, cx: &mut EventContext, drag_status: RangeDragStatus) {
+        if drag_status != RangeDragStatus::High {
+            self.low.begin_set_parameter(cx);
+        }
+        if drag_status != RangeDragStatus::Low {
+            self.high.begin_set_parameter(cx);
+        }
+    }
+
+    fn end_set_parameters(&self, cx: &mut EventContext, drag_status: RangeDragStatus) {
+        if drag_status != RangeDragStatus::High {
+            self.low.end_set_parameter(cx);
+        }
+        if drag_status != RangeDragStatus::Low {
+            self.high.end_set_parameter(cx);
+        }
+    }
+}
+
+impl View for RangeSlider {
+    fn element(&self) -> Option<&'static str> {
+        Some("range-slider")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match window_event {
+            // See `TimeSlider` for why triple clicks are treated as regular clicks
+            WindowEvent::MouseDown(MouseButton::Left)
+            | WindowEvent::MouseTripleClick(MouseButton::Left) => {
+                let t = remap_current_entity_x_coordinate(cx, cx.mouse.cursorx);
+                let drag_status = self.drag_target(cx, cx.mouse.cursorx);
+
+                cx.capture();
+                cx.set_active(true);
+
+                self.begin_set_parameters(cx, drag_status);
+                // Clicking on the track moves the closest handle there right away, while the
+                // window only moves once the mouse does
+                self.drag_to(cx, drag_status, t);
+                self.drag_status = Some(drag_status);
+
+                meta.consume();
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left)
+            | WindowEvent::MouseDown(MouseButton::Right)
+            | WindowEvent::MouseDoubleClick(MouseButton::Right)
+            | WindowEvent::MouseTripleClick(MouseButton::Right) => {
+                // Double click and right clicks reset both ends of the range
+                for param_base in [&self.low, &self.high] {
+                    param_base.begin_set_parameter(cx);
+                    param_base.set_normalized_value(cx, param_base.default_normalized_value());
+                    param_base.end_set_parameter(cx);
+                }
+
+                meta.consume();
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if let Some(drag_status) = self.drag_status.take() {
+                    cx.release();
+                    cx.set_active(false);
+
+                    self.end_set_parameters(cx, drag_status);
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(x, _y) => {
+                if let Some(drag_status) = self.drag_status {
+                    let t = remap_current_entity_x_coordinate(cx, *x);
+                    self.drag_to(cx, drag_status, t);
+                }
+            }
+            _ => {}
+        });
+    }
+}
+
+/// The track, the highlighted region between the handles, and the handles themselves. The track
+/// uses the background color, and the region and handles use the font color.
+struct RangeTrack<LLow, LHigh>
+where
+    LLow: Lens<Target = f32>,
+    LHigh: Lens<Target = f32>,
+{
+    low: LLow,
+    high: LHigh,
+}
+
+impl<LLow, LHigh> View for RangeTrack<LLow, LHigh>
+where
+    LLow: Lens<Target = f32>,
+    LHigh: Lens<Target = f32>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("range-track")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let dpi_scale = cx.scale_factor();
+        let opacity = cx.opacity();
+        let mut background_color: vg::Color = cx.background_color().into();
+        background_color.set_alphaf(background_color.a * opacity);
+        let mut handle_color: vg::Color = cx.font_color().into();
+        handle_color.set_alphaf(handle_color.a * opacity);
+        let mut region_color = handle_color;
+        region_color.set_alphaf(handle_color.a * 0.35);
+
+        let mut path = vg::Path::new();
+        path.rect(bounds.x, bounds.y, bounds.w, bounds.h);
+        canvas.fill_path(&mut path, &vg::Paint::color(background_color));
+
+        // If the parameters have crossed through automation the region is drawn between them
+        // anyways, which matches how the plugin should treat them
+        let low = self.low.get(cx);
+        let high = self.high.get(cx);
+        let (start_t, end_t) = (low.min(high), low.max(high));
+        let mut path = vg::Path::new();
+        path.rect(
+            bounds.x + start_t * bounds.w,
+            bounds.y,
+            (end_t - start_t) * bounds.w,
+            bounds.h,
+        );
+        canvas.fill_path(&mut path, &vg::Paint::color(region_color));
+
+        let handle_width = HANDLE_WIDTH * dpi_scale;
+        let mut path = vg::Path::new();
+        for t in [low, high] {
+            let x = bounds.x + t * (bounds.w - handle_width);
+            path.rect(x, bounds.y, handle_width, bounds.h);
+        }
+        canvas.fill_path(&mut path, &vg::Paint::color(handle_color));
+    }
+}
@@ -0,0 +1,112 @@
+//! Grids that widgets can snap their values to while dragging.
+
+use atomic_float::AtomicF32;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Plain values a widget like [`TimeSlider`][crate::time_slider::TimeSlider] snaps to while
+/// dragging.
+#[derive(Clone)]
+pub enum SnapGrid {
+    /// Snap to the closest of these plain values.
+    Values(Arc<[f32]>),
+    /// Map a plain value to the plain value it should snap to. This is called for every drag
+    /// update, so the grid can follow changing state like the host's tempo.
+    Function(Arc<dyn Fn(f32) -> f32>),
+}
+
+impl SnapGrid {
+    /// Snap to the closest of `values`.
+    pub fn values(values: impl Into<Arc<[f32]>>) -> Self {
+        SnapGrid::Values(values.into())
+    }
+
+    /// Snap using a function that maps a plain value to its snapped plain value.
+    pub fn function(f: impl Fn(f32) -> f32 + 'static) -> Self {
+        SnapGrid::Function(Arc::new(f))
+    }
+
+    /// Snap a time in seconds to multiples of `beat_fraction` beats at the tempo in beats per
+    /// minute stored in `tempo`. For instance, a `beat_fraction` of `0.25` snaps to sixteenth notes
+    /// when a beat is a quarter note. Nothing is snapped while the tempo is unknown.
+    pub fn beats(tempo: Arc<AtomicF32>, beat_fraction: f32) -> Self {
+        Self::function(move |seconds| {
+            let tempo = tempo.load(Ordering::Relaxed);
+            if tempo <= 0.0 || beat_fraction <= 0.0 {
+                return seconds;
+            }
+
+            let step = beat_fraction * 60.0 / tempo;
+            (seconds / step).round() * step
+        })
+    }
+
+    /// Snap a plain value to this grid.
+    pub fn snap(&self, plain_value: f32) -> f32 {
+        match self {
+            SnapGrid::Values(values) => values
+                .iter()
+                .copied()
+                .min_by(|a, b| (a - plain_value).abs().total_cmp(&(b - plain_value).abs()))
+                .unwrap_or(plain_value),
+            SnapGrid::Function(f) => f(plain_value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_snap_to_the_closest_value() {
+        let grid = SnapGrid::values([0.1, 0.25, 0.5, 1.0]);
+
+        assert_eq!(grid.snap(0.0), 0.1);
+        assert_eq!(grid.snap(0.2), 0.25);
+        assert_eq!(grid.snap(0.4), 0.5);
+        assert_eq!(grid.snap(0.5), 0.5);
+        assert_eq!(grid.snap(0.8), 1.0);
+        assert_eq!(grid.snap(10.0), 1.0);
+    }
+
+    #[test]
+    fn empty_values_dont_snap() {
+        let grid = SnapGrid::values(Vec::new());
+
+        assert_eq!(grid.snap(0.3), 0.3);
+    }
+
+    #[test]
+    fn functions_snap_to_their_result() {
+        let grid = SnapGrid::function(|value| (value * 10.0).round() / 10.0);
+
+        assert_eq!(grid.snap(0.26), 0.3);
+        assert_eq!(grid.snap(0.04), 0.0);
+    }
+
+    #[test]
+    fn beats_follow_the_tempo() {
+        let tempo = Arc::new(AtomicF32::new(120.0));
+        // Sixteenth notes last 0.125 seconds at 120 BPM
+        let grid = SnapGrid::beats(tempo.clone(), 0.25);
+
+        assert_eq!(grid.snap(0.3), 0.25);
+        assert_eq!(grid.snap(0.32), 0.375);
+        assert_eq!(grid.snap(2.0), 2.0);
+
+        // And 0.25 seconds at 60 BPM
+        tempo.store(60.0, Ordering::Relaxed);
+        assert_eq!(grid.snap(0.3), 0.25);
+        assert_eq!(grid.snap(0.4), 0.5);
+    }
+
+    #[test]
+    fn beats_dont_snap_without_a_tempo() {
+        let tempo = Arc::new(AtomicF32::new(0.0));
+
+        assert_eq!(SnapGrid::beats(tempo.clone(), 0.25).snap(0.3), 0.3);
+        tempo.store(120.0, Ordering::Relaxed);
+        assert_eq!(SnapGrid::beats(tempo, 0.0).snap(0.3), 0.3);
+    }
+}
@@ -0,0 +1,349 @@
+//! Formatting time values for display in widgets like [`TimeSlider`][crate::time_slider::TimeSlider].
+
+use atomic_float::AtomicF32;
+use nih_plug_vizia::vizia::prelude::*;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::value_formatter::{FormatterParam, ValueFormatter};
+
+/// The note lengths that are tried when displaying a time as a musical note division, as
+/// denominators of a whole note.
+const NOTE_DENOMINATORS: [u32; 7] = [1, 2, 4, 8, 16, 32, 64];
+/// How close a time needs to be to a note division, in whole notes, to be displayed as that
+/// division.
+const NOTE_DIVISION_TOLERANCE: f32 = 1e-3;
+
+/// How a time value should be displayed. The parameter's plain value is interpreted as a time in
+/// seconds.
+#[derive(Debug, Clone, Default)]
+pub enum TimeFormat {
+    /// Whole milliseconds, e.g. `250 ms`.
+    Milliseconds,
+    /// Seconds with two decimals, e.g. `1.50 s`.
+    #[default]
+    Seconds,
+    /// Minutes, seconds and milliseconds, e.g. `01:30.250`.
+    MinutesSecondsMillis,
+    /// Hours and minutes, e.g. `12:30`. Only useful for very long times.
+    HoursMinutes,
+    /// The number of samples at the sample rate stored in `sample_rate`. This is shared with the
+    /// plugin so it can be updated when the sample rate changes.
+    Samples { sample_rate: Arc<AtomicF32> },
+    /// A note division like `1/8`, `1/8d` (dotted), or `1/8t` (triplet) at the tempo in beats per
+    /// minute stored in `tempo`, assuming a beat is a quarter note. Times that don't line up with
+    /// a note division are shown as a number of beats instead.
+    Beats { tempo: Arc<AtomicF32> },
+}
+
+impl PartialEq for TimeFormat {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (TimeFormat::Samples { sample_rate: a }, TimeFormat::Samples { sample_rate: b }) => {
+                Arc::ptr_eq(a, b)
+            }
+            (TimeFormat::Beats { tempo: a }, TimeFormat::Beats { tempo: b }) => Arc::ptr_eq(a, b),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Data for TimeFormat {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl TimeFormat {
+    /// Format a time in seconds.
+    pub fn format_seconds(&self, seconds: f32) -> String {
+        match self {
+            TimeFormat::Milliseconds => format!("{:.0} ms", seconds * 1000.0),
+            TimeFormat::Seconds => format!("{seconds:.2} s"),
+            TimeFormat::MinutesSecondsMillis => {
+                let total_millis = (seconds * 1000.0).round().max(0.0) as u32;
+                let minutes = total_millis / 60_000;
+                let seconds = (total_millis / 1000) % 60;
+                let millis = total_millis % 1000;
+
+                format!("{minutes:02}:{seconds:02}.{millis:03}")
+            }
+            TimeFormat::HoursMinutes => {
+                let total_minutes = (seconds / 60.0).round().max(0.0) as u32;
+                let hours = total_minutes / 60;
+                let minutes = total_minutes % 60;
+
+                format!("{hours:02}:{minutes:02}")
+            }
+            TimeFormat::Samples { sample_rate } => {
+                format!(
+                    "{:.0} samples",
+                    seconds * sample_rate.load(Ordering::Relaxed)
+                )
+            }
+            TimeFormat::Beats { tempo } => {
+                let beats = seconds * tempo.load(Ordering::Relaxed) / 60.0;
+                match format_note_division(beats / 4.0) {
+                    Some(division) => division,
+                    None => format!("{beats:.2} beats"),
+                }
+            }
+        }
+    }
+
+    /// Parse a time typed by the user back to seconds. This accepts all of the formats produced by
+    /// [`format_seconds()`][Self::format_seconds()] regardless of which format is selected, e.g.
+    /// `250ms`, `1.5s`, `01:30.250`, or `3/16`. Samples and note divisions can only be parsed when
+    /// the format carries a sample rate or tempo. A bare number is interpreted in this format's
+    /// unit, and a bare `12:30` is read as hours and minutes for [`TimeFormat::HoursMinutes`] and
+    /// as minutes and seconds otherwise.
+    pub fn parse_seconds(&self, string: &str) -> Option<f32> {
+        let string = string.trim().to_lowercase();
+        let seconds = if let Some(millis) = strip_unit(&string, &["ms"]) {
+            parse_number(millis)? / 1000.0
+        } else if let Some(samples) = strip_unit(&string, &["samples", "sample", "smp"]) {
+            self.samples_to_seconds(parse_number(samples)?)?
+        } else if let Some(beats) = strip_unit(&string, &["beats", "beat"]) {
+            self.beats_to_seconds(parse_number(beats)?)?
+        } else if string.contains('/') {
+            self.beats_to_seconds(parse_note_division(&string)? * 4.0)?
+        } else if string.contains(':') {
+            parse_clock(&string, matches!(self, TimeFormat::HoursMinutes))?
+        } else if let Some(seconds) = strip_unit(&string, &["s", "sec"]) {
+            parse_number(seconds)?
+        } else {
+            let value = parse_number(&string)?;
+            match self {
+                TimeFormat::Milliseconds => value / 1000.0,
+                TimeFormat::Seconds | TimeFormat::MinutesSecondsMillis => value,
+                TimeFormat::HoursMinutes => value * 60.0,
+                TimeFormat::Samples { .. } => self.samples_to_seconds(value)?,
+                TimeFormat::Beats { .. } => self.beats_to_seconds(value)?,
+            }
+        };
+
+        if seconds.is_finite() && seconds >= 0.0 {
+            Some(seconds)
+        } else {
+            None
+        }
+    }
+
+    fn samples_to_seconds(&self, samples: f32) -> Option<f32> {
+        match self {
+            TimeFormat::Samples { sample_rate } => {
+                let sample_rate = sample_rate.load(Ordering::Relaxed);
+                (sample_rate > 0.0).then(|| samples / sample_rate)
+            }
+            _ => None,
+        }
+    }
+
+    fn beats_to_seconds(&self, beats: f32) -> Option<f32> {
+        match self {
+            TimeFormat::Beats { tempo } => {
+                let tempo = tempo.load(Ordering::Relaxed);
+                (tempo > 0.0).then(|| beats * 60.0 / tempo)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ValueFormatter for TimeFormat {
+    fn format(&self, param: &FormatterParam, normalized_value: f32) -> String {
+        self.format_seconds(param.preview_plain(normalized_value))
+    }
+
+    fn parse(&self, param: &FormatterParam, string: &str) -> Option<f32> {
+        // The parameter's own string conversion is used as a fallback
+        match self.parse_seconds(string) {
+            Some(seconds) => Some(param.preview_normalized(seconds)),
+            None => param.string_to_normalized_value(string),
+        }
+    }
+}
+
+/// Strip the first matching unit suffix from `string`, returning the remaining number part.
+fn strip_unit<'a>(string: &'a str, units: &[&str]) -> Option<&'a str> {
+    units
+        .iter()
+        .find_map(|unit| string.strip_suffix(unit))
+        .map(str::trim_end)
+}
+
+fn parse_number(string: &str) -> Option<f32> {
+    string.trim().parse().ok()
+}
+
+/// Parse a note division like `3/16`, `1/8d`, or `1/8t` to a length in whole notes.
+fn parse_note_division(string: &str) -> Option<f32> {
+    let (string, scale) = if let Some(string) = string.strip_suffix('d') {
+        (string, 1.5)
+    } else if let Some(string) = string.strip_suffix('t') {
+        (string, 2.0 / 3.0)
+    } else {
+        (string, 1.0)
+    };
+
+    let (numerator, denominator) = string.split_once('/')?;
+    let numerator = parse_number(numerator)?;
+    let denominator = parse_number(denominator)?;
+    if denominator <= 0.0 {
+        return None;
+    }
+
+    Some(numerator / denominator * scale)
+}
+
+/// Parse `HH:MM:SS(.mmm)`, or `MM:SS(.mmm)` or `HH:MM` depending on `hours_minutes`, to seconds.
+fn parse_clock(string: &str, hours_minutes: bool) -> Option<f32> {
+    let mut parts = [0.0; 3];
+    let mut num_parts = 0;
+    for part in string.split(':') {
+        *parts.get_mut(num_parts)? = parse_number(part)?;
+        num_parts += 1;
+    }
+
+    match num_parts {
+        2 if hours_minutes => Some(parts[0] * 3600.0 + parts[1] * 60.0),
+        2 => Some(parts[0] * 60.0 + parts[1]),
+        3 => Some(parts[0] * 3600.0 + parts[1] * 60.0 + parts[2]),
+        _ => None,
+    }
+}
+
+/// Format a length in whole notes as a straight, dotted, or triplet note division if it lines up
+/// with one.
+fn format_note_division(whole_notes: f32) -> Option<String> {
+    // Single notes are preferred, so a dotted eighth note is shown as `1/8d` rather than as `3/16`.
+    // Straight divisions are preferred over dotted and triplet divisions when a time matches
+    // multiple of them.
+    for (scale, suffix) in [(1.0, ""), (1.0 / 1.5, "d"), (1.5, "t")] {
+        for denominator in NOTE_DENOMINATORS {
+            if note_numerator(whole_notes * scale, denominator) == Some(1) {
+                return Some(format!("1/{denominator}{suffix}"));
+            }
+        }
+    }
+
+    // Anything else is shown as a multiple of a straight division, like `5/16` or `2/1`
+    NOTE_DENOMINATORS.into_iter().find_map(|denominator| {
+        note_numerator(whole_notes, denominator)
+            .map(|numerator| format!("{numerator}/{denominator}"))
+    })
+}
+
+/// The number of `1/denominator` notes in `whole_notes`, if that's a whole number of at least one.
+fn note_numerator(whole_notes: f32, denominator: u32) -> Option<u32> {
+    let numerator = whole_notes * denominator as f32;
+    let rounded = numerator.round();
+    if rounded >= 1.0 && (numerator - rounded).abs() / denominator as f32 <= NOTE_DIVISION_TOLERANCE
+    {
+        Some(rounded as u32)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beats(tempo: f32) -> TimeFormat {
+        TimeFormat::Beats {
+            tempo: Arc::new(AtomicF32::new(tempo)),
+        }
+    }
+
+    fn assert_parses(format: &TimeFormat, string: &str, expected: Option<f32>) {
+        match (format.parse_seconds(string), expected) {
+            (Some(seconds), Some(expected)) => assert!(
+                (seconds - expected).abs() <= 1e-5 * expected.max(1.0),
+                "'{string}' parsed as {seconds} instead of {expected}"
+            ),
+            (seconds, expected) => assert_eq!(seconds, expected, "'{string}'"),
+        }
+    }
+
+    #[test]
+    fn parse_seconds() {
+        let at_120_bpm = beats(120.0);
+        let samples_at_48k = TimeFormat::Samples {
+            sample_rate: Arc::new(AtomicF32::new(48_000.0)),
+        };
+        let cases: &[(&TimeFormat, &str, Option<f32>)] = &[
+            (&TimeFormat::Seconds, "250ms", Some(0.25)),
+            (&TimeFormat::Seconds, "250 ms", Some(0.25)),
+            (&TimeFormat::Seconds, "1.5s", Some(1.5)),
+            (&TimeFormat::Seconds, " 1.5 sec ", Some(1.5)),
+            (&TimeFormat::Seconds, "1.5", Some(1.5)),
+            (&TimeFormat::Milliseconds, "1.5", Some(0.0015)),
+            (&TimeFormat::Seconds, "12:30", Some(750.0)),
+            (&TimeFormat::HoursMinutes, "12:30", Some(45_000.0)),
+            (&TimeFormat::HoursMinutes, "90", Some(5400.0)),
+            (&TimeFormat::MinutesSecondsMillis, "01:30.250", Some(90.25)),
+            (&TimeFormat::Seconds, "1:00:30", Some(3630.0)),
+            (&samples_at_48k, "24000 samples", Some(0.5)),
+            (&samples_at_48k, "480", Some(0.01)),
+            // A beat is a quarter note, so at 120 BPM a whole note lasts two seconds
+            (&at_120_bpm, "3/16", Some(0.375)),
+            (&at_120_bpm, "1/8d", Some(0.375)),
+            (&at_120_bpm, "1/8t", Some(1.0 / 6.0)),
+            (&at_120_bpm, "1/4", Some(0.5)),
+            (&at_120_bpm, "2 beats", Some(1.0)),
+            (&at_120_bpm, "2", Some(1.0)),
+            // Invalid input
+            (&TimeFormat::Seconds, "-1", None),
+            (&TimeFormat::Seconds, "-250ms", None),
+            (&TimeFormat::Seconds, "abc", None),
+            (&TimeFormat::Seconds, "", None),
+            (&TimeFormat::Seconds, "1:2:3:4", None),
+            (&at_120_bpm, "1/0", None),
+            (&at_120_bpm, "1/", None),
+            (&at_120_bpm, "x/8", None),
+            // Samples and note divisions need a sample rate or a tempo
+            (&TimeFormat::Seconds, "3/16", None),
+            (&TimeFormat::Seconds, "480 samples", None),
+            (&beats(0.0), "1/4", None),
+        ];
+
+        for (format, string, expected) in cases {
+            assert_parses(format, string, *expected);
+        }
+    }
+
+    #[test]
+    fn format_seconds() {
+        assert_eq!(TimeFormat::Milliseconds.format_seconds(0.25), "250 ms");
+        assert_eq!(TimeFormat::Seconds.format_seconds(1.5), "1.50 s");
+        assert_eq!(
+            TimeFormat::MinutesSecondsMillis.format_seconds(90.25),
+            "01:30.250"
+        );
+        assert_eq!(TimeFormat::HoursMinutes.format_seconds(45_000.0), "12:30");
+    }
+
+    #[test]
+    fn format_note_divisions() {
+        let at_120_bpm = beats(120.0);
+        let cases = [
+            (0.25, "1/8"),
+            (2.0, "1/1"),
+            (0.375, "1/8d"),
+            (0.75, "1/4d"),
+            (1.0 / 6.0, "1/8t"),
+            (1.0 / 3.0, "1/4t"),
+            (0.625, "5/16"),
+            (4.0, "2/1"),
+            (0.3, "0.60 beats"),
+        ];
+
+        for (seconds, expected) in cases {
+            assert_eq!(at_120_bpm.format_seconds(seconds), expected, "{seconds} s");
+            if !expected.ends_with("beats") {
+                assert_parses(&at_120_bpm, expected, Some(seconds));
+            }
+        }
+    }
+}
@@ -1,29 +1,52 @@
 //! A slider that integrates with NIH-plug's [`Param`] types.
 
-use nih_plug::prelude::Param;
+use nih_plug::prelude::{Param, ParamPtr};
 use nih_plug_vizia::vizia::prelude::*;
 
 use nih_plug_vizia::widgets::param_base::ParamWidgetBase;
 use nih_plug_vizia::widgets::util::remap_current_entity_x_coordinate;
 use nih_plug_vizia::widgets::util::remap_current_entity_x_t;
+use nih_plug_vizia::widgets::util::remap_current_entity_y_coordinate;
+use nih_plug_vizia::widgets::util::remap_current_entity_y_t;
 use nih_plug_vizia::widgets::util::ModifiersExt;
 
+use crate::snap_grid::SnapGrid;
+use crate::time_format::TimeFormat;
+use crate::value_formatter::{FormatterParam, SharedFormatter, ValueFormatter};
+
 /// When shift+dragging a parameter, one pixel dragged corresponds to this much change in the
 /// normalized parameter.
-const GRANULAR_DRAG_MULTIPLIER: f32 = 0.1;
+pub(crate) const GRANULAR_DRAG_MULTIPLIER: f32 = 0.1;
+/// The length of the tick marks as a percentage of the slider's height, or its width for vertical
+/// sliders.
+const TICK_LENGTH_PERCENTAGE: f32 = 25.0;
+/// The width of a tick label. Labels are centered on their tick mark.
+const TICK_LABEL_WIDTH: f32 = 60.0;
+/// The height of a tick label.
+const TICK_LABEL_HEIGHT: f32 = 14.0;
 
 /// A slider that integrates with NIH-plug's [`Param`] types. Use the
 /// [`set_style()`][ParamSliderExt::set_style()] method to change how the value gets displayed.
 #[derive(Lens)]
 pub struct TimeSlider {
     param_base: ParamWidgetBase,
+    /// Needed to give the [`ValueFormatter`] access to the parameter when parsing the text input,
+    /// since [`ParamWidgetBase`] can only parse strings using the parameter's own formatting.
+    param_ptr: ParamPtr,
 
     /// Will be set to `true` when the field gets Alt+Click'ed which will replace the label with a
     /// text box.
     text_input_active: bool,
+    /// Will be set to `true` when the text entered in the text box could not be parsed. The text
+    /// box stays open and gets highlighted until a valid value is entered or the input is
+    /// cancelled.
+    text_input_invalid: bool,
     /// Will be set to `true` if we're dragging the parameter. Resetting the parameter or entering a
     /// text value should not initiate a drag.
     drag_active: bool,
+    /// The normalized value when the current drag was started. Pressing Escape while dragging
+    /// restores this value.
+    drag_starting_value: f32,
     /// We keep track of the start coordinate and normalized value when holding down Shift while
     /// dragging for higher precision dragging. This is a `None` value when granular dragging is not
     /// active.
@@ -35,10 +58,18 @@ pub struct TimeSlider {
     /// The number of (fractional) scrolled lines that have not yet been turned into parameter
     /// change events. This is needed to support trackpads with smooth scrolling.
     scrolled_lines: f32,
-    /// What style to use for the slider.
-    style: TimeSliderStyle,
+    /// What style and orientation to use for the slider.
+    layout: TimeSliderLayout,
+    /// Converts the parameter's value to the displayed text and parses the text input. This is a
+    /// [`TimeFormat`] by default.
+    formatter: SharedFormatter,
     /// A specific label to use instead of displaying the parameter's value.
     label_override: Option<String>,
+    /// Plain values to snap to while dragging. Holding down Ctrl/Cmd while dragging temporarily
+    /// disables snapping.
+    snap_grid: Option<SnapGrid>,
+    /// Plain values to draw tick marks and labels at.
+    ticks: Vec<f32>,
 }
 
 /// How the [`ParamSlider`] should display its values. Set this using
@@ -62,6 +93,27 @@ pub enum TimeSliderStyle {
     CurrentStepLabeled { even: bool },
 }
 
+/// Which direction the [`TimeSlider`] is dragged in. Set this using
+/// [`TimeSliderExt::set_orientation()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum TimeSliderOrientation {
+    /// The minimum value is on the left and the bar fills towards the right.
+    Horizontal,
+    /// The minimum value is at the bottom and the bar fills upwards, like a fader.
+    Vertical,
+}
+
+/// Everything that changes how the [`TimeSlider`]'s views are built. These are grouped together so
+/// changing any of them rebuilds the slider through a single binding.
+// TODO: Vizia's lens derive macro requires this to be marked as pub
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub struct TimeSliderLayout {
+    pub style: TimeSliderStyle,
+    pub orientation: TimeSliderOrientation,
+    /// Don't draw the value on top of the slider.
+    pub compact: bool,
+}
+
 enum TimeSliderEvent {
     /// Text input has been cancelled without submitting a new value.
     CancelTextInput,
@@ -72,8 +124,9 @@ enum TimeSliderEvent {
 // TODO: Vizia's lens derive macro requires this to be marked as pub
 #[derive(Debug, Clone, Copy)]
 pub struct GranularDragStatus {
-    /// The mouse's X-coordinate when the granular drag was started.
-    pub starting_x_coordinate: f32,
+    /// The mouse's X-coordinate, or the Y-coordinate for vertical sliders, when the granular drag
+    /// was started.
+    pub starting_coordinate: f32,
     /// The normalized value when the granular drag was started.
     pub starting_value: f32,
 }
@@ -101,118 +154,177 @@ impl TimeSlider {
         // We'll visualize the difference between the current value and the default value if the
         // default value lies somewhere in the middle and the parameter is continuous. Otherwise
         // this approach looks a bit jarring.
+        // This is the same trick `ParamWidgetBase` uses to get a pointer to the parameter without
+        // any lifetime bounds
+        let param_ptr = params
+            .clone()
+            .map(move |params| params_to_param(params).as_ptr())
+            .get(cx);
+
         Self {
             param_base: ParamWidgetBase::new(cx, params.clone(), params_to_param),
+            param_ptr,
 
             text_input_active: false,
+            text_input_invalid: false,
             drag_active: false,
+            drag_starting_value: 0.0,
             granular_drag_status: None,
 
             use_scroll_wheel: true,
             scrolled_lines: 0.0,
-            style: TimeSliderStyle::Centered,
+            layout: TimeSliderLayout {
+                style: TimeSliderStyle::Centered,
+                orientation: TimeSliderOrientation::Horizontal,
+                compact: false,
+            },
+            formatter: SharedFormatter::new(TimeFormat::default()),
             label_override: None,
+            snap_grid: None,
+            ticks: Vec::new(),
         }
         .build(
             cx,
             ParamWidgetBase::build_view(params, params_to_param, move |cx, param_data| {
-                Binding::new(cx, TimeSlider::style, move |cx, style| {
-                    let style = style.get(cx);
-
-                    // Needs to be moved into the below closures, and it can't be `Copy`
+                Binding::new(cx, TimeSlider::formatter, move |cx, formatter| {
+                    let formatter = formatter.get(cx);
                     let param_data = param_data.clone();
 
-                    // Can't use `.to_string()` here as that would include the modulation.
-                    let unmodulated_normalized_value_lens =
-                        param_data.make_lens(|param| param.unmodulated_normalized_value());
-                    let display_value_lens = param_data.make_lens(|param| {
-                        let total_minutes = param.unmodulated_normalized_value() * 1440.0; // 24 hours * 60 minutes
-                        let hours = total_minutes as u32 / 60;
-                        let minutes = total_minutes as u32 % 60;
-
-                        format!("{:02}:{:02}", hours, minutes)
-                    });
+                    Binding::new(cx, TimeSlider::layout, move |cx, layout| {
+                        let layout = layout.get(cx);
+                        let style = layout.style;
 
-                    // The resulting tuple `(start_t, delta)` corresponds to the start and the
-                    // signed width of the bar. `start_t` is in `[0, 1]`, and `delta` is in
-                    // `[-1, 1]`.
-                    let fill_start_delta_lens = {
+                        // Needs to be moved into the below closures, and it can't be `Copy`
                         let param_data = param_data.clone();
-                        unmodulated_normalized_value_lens.map(move |current_value| {
-                            Self::compute_fill_start_delta(
-                                style,
-                                param_data.param(),
-                                *current_value,
-                            )
-                        })
-                    };
-
-                    // If the parameter is being modulated by the host (this only works for CLAP
-                    // plugins with hosts that support this), then this is the difference
-                    // between the 'true' value and the current value after modulation has been
-                    // applied. This follows the same format as `fill_start_delta_lens`.
-                    let modulation_start_delta_lens = param_data.make_lens(move |param| {
-                        Self::compute_modulation_fill_start_delta(style, param)
-                    });
+                        let formatter = formatter.clone();
 
-                    // This is used to draw labels for `CurrentStepLabeled`
-                    let make_preview_value_lens = {
-                        let param_data = param_data.clone();
-                        move |normalized_value| {
+                        // Can't use `.to_string()` here as that would include the modulation.
+                        let unmodulated_normalized_value_lens =
+                            param_data.make_lens(|param| param.unmodulated_normalized_value());
+                        let display_value_lens = {
+                            let formatter = formatter.clone();
                             param_data.make_lens(move |param| {
-                                param.normalized_value_to_string(normalized_value, true)
+                                formatter.format(
+                                    &FormatterParam::new(param),
+                                    param.unmodulated_normalized_value(),
+                                )
+                            })
+                        };
+
+                        // The resulting tuple `(start_t, delta)` corresponds to the start and the
+                        // signed width of the bar. `start_t` is in `[0, 1]`, and `delta` is in
+                        // `[-1, 1]`.
+                        let fill_start_delta_lens = {
+                            let param_data = param_data.clone();
+                            unmodulated_normalized_value_lens.map(move |current_value| {
+                                Self::compute_fill_start_delta(
+                                    style,
+                                    param_data.param(),
+                                    *current_value,
+                                )
                             })
-                        }
-                    };
-
-                    // Only draw the text input widget when it gets focussed. Otherwise, overlay the
-                    // label with the slider. Creating the textbox based on
-                    // `ParamSliderInternal::text_input_active` lets us focus the textbox when it gets
-                    // created.
-                    Binding::new(
-                        cx,
-                        TimeSlider::text_input_active,
-                        move |cx, text_input_active| {
-                            if text_input_active.get(cx) {
-                                Self::text_input_view(cx, display_value_lens.clone());
-                            } else {
-                                // All of this data needs to be moved into the `ZStack` closure, and
-                                // the `Map` lens combinator isn't `Copy`
-                                let param_data = param_data.clone();
-                                let fill_start_delta_lens = fill_start_delta_lens.clone();
-                                let modulation_start_delta_lens =
-                                    modulation_start_delta_lens.clone();
-                                let display_value_lens = display_value_lens.clone();
-                                let make_preview_value_lens = make_preview_value_lens.clone();
-
-                                ZStack::new(cx, move |cx| {
-                                    Self::slider_fill_view(
-                                        cx,
-                                        fill_start_delta_lens,
-                                        modulation_start_delta_lens,
-                                    );
-                                    Self::slider_label_view(
-                                        cx,
-                                        param_data.param(),
-                                        style,
-                                        display_value_lens,
-                                        make_preview_value_lens,
-                                        TimeSlider::label_override,
-                                    );
+                        };
+
+                        // If the parameter is being modulated by the host (this only works for CLAP
+                        // plugins with hosts that support this), then this is the difference
+                        // between the 'true' value and the current value after modulation has been
+                        // applied. This follows the same format as `fill_start_delta_lens`.
+                        let modulation_start_delta_lens = param_data.make_lens(move |param| {
+                            Self::compute_modulation_fill_start_delta(style, param)
+                        });
+
+                        // This is used to draw labels for `CurrentStepLabeled`
+                        let make_preview_value_lens = {
+                            let param_data = param_data.clone();
+                            let formatter = formatter.clone();
+                            move |normalized_value| {
+                                let formatter = formatter.clone();
+                                param_data.make_lens(move |param| {
+                                    formatter.format(&FormatterParam::new(param), normalized_value)
                                 })
-                                .hoverable(false);
                             }
-                        },
-                    );
+                        };
+
+                        // Only draw the text input widget when it gets focussed. Otherwise, overlay the
+                        // label with the slider. Creating the textbox based on
+                        // `ParamSliderInternal::text_input_active` lets us focus the textbox when it gets
+                        // created.
+                        Binding::new(
+                            cx,
+                            TimeSlider::text_input_active,
+                            move |cx, text_input_active| {
+                                if text_input_active.get(cx) {
+                                    Self::text_input_view(cx, display_value_lens.clone());
+                                } else {
+                                    // All of this data needs to be moved into the `ZStack` closure, and
+                                    // the `Map` lens combinator isn't `Copy`
+                                    let param_data = param_data.clone();
+                                    let fill_start_delta_lens = fill_start_delta_lens.clone();
+                                    let modulation_start_delta_lens =
+                                        modulation_start_delta_lens.clone();
+                                    let display_value_lens = display_value_lens.clone();
+                                    let make_preview_value_lens = make_preview_value_lens.clone();
+                                    let formatter = formatter.clone();
+
+                                    ZStack::new(cx, move |cx| {
+                                        Self::slider_fill_view(
+                                            cx,
+                                            layout.orientation,
+                                            fill_start_delta_lens,
+                                            modulation_start_delta_lens,
+                                        );
+                                        {
+                                            let param_data = param_data.clone();
+                                            Binding::new(
+                                                cx,
+                                                TimeSlider::ticks,
+                                                move |cx, ticks| {
+                                                    Self::tick_view(
+                                                        cx,
+                                                        param_data.param(),
+                                                        &formatter,
+                                                        layout.orientation,
+                                                        &ticks.get(cx),
+                                                    );
+                                                },
+                                            );
+                                        }
+                                        if !layout.compact {
+                                            Self::slider_label_view(
+                                                cx,
+                                                param_data.param(),
+                                                style,
+                                                display_value_lens,
+                                                make_preview_value_lens,
+                                                TimeSlider::label_override,
+                                            );
+                                        }
+                                    })
+                                    .hoverable(false);
+                                }
+                            },
+                        );
+                    });
                 });
             }),
         )
+        // Allows focussing the slider with Tab for keyboard control
+        .navigable(true)
     }
 
     /// Create a text input that's shown in place of the slider.
     fn text_input_view(cx: &mut Context, display_value_lens: impl Lens<Target = String>) {
         Textbox::new(cx, display_value_lens)
             .class("value-entry")
+            .toggle_class("value-entry--invalid", TimeSlider::text_input_invalid)
+            .border_color(TimeSlider::text_input_invalid.map(|invalid| {
+                if *invalid {
+                    Color::rgb(220, 50, 47)
+                } else {
+                    Color::transparent()
+                }
+            }))
+            .border_width(Pixels(1.0))
             .on_submit(|cx, string, success| {
                 if success {
                     cx.emit(TimeSliderEvent::TextInput(string))
@@ -235,51 +347,151 @@ impl TimeSlider {
     /// Create the fill part of the slider.
     fn slider_fill_view(
         cx: &mut Context,
+        orientation: TimeSliderOrientation,
         fill_start_delta_lens: impl Lens<Target = (f32, f32)>,
         modulation_start_delta_lens: impl Lens<Target = (f32, f32)>,
     ) {
         // The filled bar portion. This can be visualized in a couple different ways depending on
         // the current style property. See [`ParamSliderStyle`].
-        Element::new(cx)
+        let fill = Element::new(cx)
             .class("fill")
-            .height(Stretch(1.0))
-            .left(
-                fill_start_delta_lens
-                    .clone()
-                    .map(|(start_t, _)| Percentage(start_t * 100.0)),
-            )
-            .width(fill_start_delta_lens.map(|(_, delta)| Percentage(delta * 100.0)))
             // Hovering is handled on the param slider as a whole, this
             // should not affect that
             .hoverable(false);
+        Self::position_fill(fill, orientation, fill_start_delta_lens);
 
         // If the parameter is being modulated, then we'll display another
         // filled bar showing the current modulation delta
         // VIZIA's bindings make this a bit, uh, difficult to read
-        Element::new(cx)
+        let modulation_fill = Element::new(cx)
             .class("fill")
             .class("fill--modulation")
-            .height(Stretch(1.0))
             .visibility(
                 modulation_start_delta_lens
                     .clone()
                     .map(|(_, delta)| *delta != 0.0),
             )
-            // Widths cannot be negative, so we need to compensate the start
-            // position if the width does happen to be negative
-            .width(
-                modulation_start_delta_lens
-                    .clone()
-                    .map(|(_, delta)| Percentage(delta.abs() * 100.0)),
-            )
-            .left(modulation_start_delta_lens.map(|(start_t, delta)| {
+            .hoverable(false);
+        // Widths cannot be negative, so we need to compensate the start
+        // position if the width does happen to be negative
+        Self::position_fill(
+            modulation_fill,
+            orientation,
+            modulation_start_delta_lens.map(|(start_t, delta)| {
                 if *delta < 0.0 {
-                    Percentage((start_t + delta) * 100.0)
+                    (start_t + delta, delta.abs())
                 } else {
-                    Percentage(start_t * 100.0)
+                    (*start_t, *delta)
                 }
-            }))
+            }),
+        );
+    }
+
+    /// Position a fill element along the slider's track. `start_size_lens` contains the start of the
+    /// filled region and its non-negative size, both as normalized values. Vertical sliders start
+    /// at the bottom.
+    fn position_fill(
+        handle: Handle<Element>,
+        orientation: TimeSliderOrientation,
+        start_size_lens: impl Lens<Target = (f32, f32)>,
+    ) {
+        match orientation {
+            TimeSliderOrientation::Horizontal => {
+                handle
+                    .height(Stretch(1.0))
+                    .left(
+                        start_size_lens
+                            .clone()
+                            .map(|(start_t, _)| Percentage(start_t * 100.0)),
+                    )
+                    .width(start_size_lens.map(|(_, size)| Percentage(size * 100.0)));
+            }
+            TimeSliderOrientation::Vertical => {
+                handle
+                    .width(Stretch(1.0))
+                    .top(
+                        start_size_lens
+                            .clone()
+                            .map(|(start_t, size)| Percentage((1.0 - start_t - size) * 100.0)),
+                    )
+                    .height(start_size_lens.map(|(_, size)| Percentage(size * 100.0)));
+            }
+        }
+    }
+
+    /// Create tick marks at the plain values in `ticks`, labelled using `formatter`. The labels are
+    /// drawn outside of the slider's bounds, below horizontal sliders and to the right of vertical
+    /// sliders.
+    fn tick_view<P: Param>(
+        cx: &mut Context,
+        param: &P,
+        formatter: &SharedFormatter,
+        orientation: TimeSliderOrientation,
+        ticks: &[f32],
+    ) {
+        let param = FormatterParam::new(param);
+        for &plain_value in ticks {
+            // Going through the normalized value puts the ticks in the right place for skewed
+            // ranges
+            let t = param.preview_normalized(plain_value);
+            let label = formatter.format(&param, t);
+
+            let (tick_mark, label_anchor) = match orientation {
+                TimeSliderOrientation::Horizontal => (
+                    Element::new(cx)
+                        .left(Percentage(t * 100.0))
+                        .top(Percentage(100.0 - TICK_LENGTH_PERCENTAGE))
+                        .width(Pixels(1.0))
+                        .height(Percentage(TICK_LENGTH_PERCENTAGE)),
+                    (Percentage(t * 100.0), Percentage(100.0)),
+                ),
+                TimeSliderOrientation::Vertical => (
+                    Element::new(cx)
+                        .left(Percentage(100.0 - TICK_LENGTH_PERCENTAGE))
+                        .top(Percentage((1.0 - t) * 100.0))
+                        .width(Percentage(TICK_LENGTH_PERCENTAGE))
+                        .height(Pixels(1.0)),
+                    (Percentage(100.0), Percentage((1.0 - t) * 100.0)),
+                ),
+            };
+            tick_mark
+                .class("tick")
+                .position_type(PositionType::SelfDirected)
+                .background_color(Color::rgba(255, 255, 255, 128))
+                .hoverable(false);
+
+            // The label is positioned relative to a zero sized anchor at the tick mark so it can be
+            // centered on it
+            let (anchor_left, anchor_top) = label_anchor;
+            VStack::new(cx, |cx| {
+                let label = Label::new(cx, &label)
+                    .class("tick-label")
+                    .position_type(PositionType::SelfDirected)
+                    .font_size(10.0)
+                    .width(Pixels(TICK_LABEL_WIDTH))
+                    .height(Pixels(TICK_LABEL_HEIGHT))
+                    .child_top(Stretch(1.0))
+                    .child_bottom(Stretch(1.0))
+                    .hoverable(false);
+                match orientation {
+                    TimeSliderOrientation::Horizontal => label
+                        .left(Pixels(-TICK_LABEL_WIDTH / 2.0))
+                        .child_left(Stretch(1.0))
+                        .child_right(Stretch(1.0)),
+                    TimeSliderOrientation::Vertical => label
+                        .left(Pixels(2.0))
+                        .top(Pixels(-TICK_LABEL_HEIGHT / 2.0))
+                        .child_left(Pixels(0.0))
+                        .child_right(Stretch(1.0)),
+                };
+            })
+            .position_type(PositionType::SelfDirected)
+            .left(anchor_left)
+            .top(anchor_top)
+            .width(Pixels(0.0))
+            .height(Pixels(0.0))
             .hoverable(false);
+        }
     }
 
     /// Create the text part of the slider. Shown on top of the fill using a `ZStack`.
@@ -412,12 +624,52 @@ impl TimeSlider {
         }
     }
 
+    /// Set the parameter to a new value in its own automation gesture, or as part of the current
+    /// gesture while dragging.
+    fn set_normalized_value_gesture(&self, cx: &mut EventContext, normalized_value: f32) {
+        if !self.drag_active {
+            self.param_base.begin_set_parameter(cx);
+        }
+        self.param_base.set_normalized_value(cx, normalized_value);
+        if !self.drag_active {
+            self.param_base.end_set_parameter(cx);
+        }
+    }
+
+    /// The mouse coordinate along the slider's track. This is the X-coordinate for horizontal
+    /// sliders and the Y-coordinate for vertical sliders.
+    fn track_coordinate(&self, x: f32, y: f32) -> f32 {
+        match self.layout.orientation {
+            TimeSliderOrientation::Horizontal => x,
+            TimeSliderOrientation::Vertical => y,
+        }
+    }
+
+    /// Remap a coordinate along the slider's track to a normalized value. Vertical sliders have
+    /// their minimum value at the bottom.
+    fn remap_track_coordinate(&self, cx: &EventContext, coordinate: f32) -> f32 {
+        match self.layout.orientation {
+            TimeSliderOrientation::Horizontal => remap_current_entity_x_coordinate(cx, coordinate),
+            TimeSliderOrientation::Vertical => {
+                1.0 - remap_current_entity_y_coordinate(cx, coordinate)
+            }
+        }
+    }
+
+    /// The inverse of [`remap_track_coordinate()`][Self::remap_track_coordinate()].
+    fn remap_track_t(&self, cx: &EventContext, t: f32) -> f32 {
+        match self.layout.orientation {
+            TimeSliderOrientation::Horizontal => remap_current_entity_x_t(cx, t),
+            TimeSliderOrientation::Vertical => remap_current_entity_y_t(cx, 1.0 - t),
+        }
+    }
+
     /// `self.param_base.set_normalized_value()`, but resulting from a mouse drag. When using the
     /// 'even' stepped slider styles from [`ParamSliderStyle`] this will remap the normalized range
-    /// to match up with the fill value display. This still needs to be wrapped in a parameter
-    /// automation gesture.
+    /// to match up with the fill value display, and the value is snapped to the snap grid if one is
+    /// set. This still needs to be wrapped in a parameter automation gesture.
     fn set_normalized_value_drag(&self, cx: &mut EventContext, normalized_value: f32) {
-        let normalized_value = match (self.style, self.param_base.step_count()) {
+        let normalized_value = match (self.layout.style, self.param_base.step_count()) {
             (
                 TimeSliderStyle::CurrentStep { even: true }
                 | TimeSliderStyle::CurrentStepLabeled { even: true },
@@ -433,6 +685,18 @@ impl TimeSlider {
             _ => normalized_value,
         };
 
+        let normalized_value = match &self.snap_grid {
+            Some(snap_grid) if !cx.modifiers.command() => {
+                // SAFETY: The parameter outlives the editor, `ParamWidgetBase` relies on the same
+                //         guarantee
+                let param = unsafe { FormatterParam::from_ptr(self.param_ptr) };
+                let snapped_value = snap_grid.snap(param.preview_plain(normalized_value));
+
+                param.preview_normalized(snapped_value)
+            }
+            _ => normalized_value,
+        };
+
         self.param_base.set_normalized_value(cx, normalized_value);
     }
 }
@@ -446,18 +710,27 @@ impl View for TimeSlider {
         event.map(|param_slider_event, meta| match param_slider_event {
             TimeSliderEvent::CancelTextInput => {
                 self.text_input_active = false;
+                self.text_input_invalid = false;
                 cx.set_active(false);
 
                 meta.consume();
             }
             TimeSliderEvent::TextInput(string) => {
-                if let Some(normalized_value) = self.param_base.string_to_normalized_value(string) {
-                    self.param_base.begin_set_parameter(cx);
-                    self.param_base.set_normalized_value(cx, normalized_value);
-                    self.param_base.end_set_parameter(cx);
-                }
+                // SAFETY: The parameter outlives the editor, `ParamWidgetBase` relies on the same
+                //         guarantee
+                let param = unsafe { FormatterParam::from_ptr(self.param_ptr) };
+                match self.formatter.parse(&param, string) {
+                    Some(normalized_value) => {
+                        self.param_base.begin_set_parameter(cx);
+                        self.param_base.set_normalized_value(cx, normalized_value);
+                        self.param_base.end_set_parameter(cx);
 
-                self.text_input_active = false;
+                        self.text_input_active = false;
+                        self.text_input_invalid = false;
+                    }
+                    // Keep the text box open so the mistake can be corrected
+                    None => self.text_input_invalid = true,
+                }
 
                 meta.consume();
             }
@@ -490,17 +763,20 @@ impl View for TimeSlider {
 
                     // When holding down shift while clicking on a parameter we want to granuarly
                     // edit the parameter without jumping to a new value
+                    self.drag_starting_value = self.param_base.unmodulated_normalized_value();
                     self.param_base.begin_set_parameter(cx);
                     if cx.modifiers.shift() {
                         self.granular_drag_status = Some(GranularDragStatus {
-                            starting_x_coordinate: cx.mouse.cursorx,
+                            starting_coordinate: self
+                                .track_coordinate(cx.mouse.cursorx, cx.mouse.cursory),
                             starting_value: self.param_base.unmodulated_normalized_value(),
                         });
                     } else {
                         self.granular_drag_status = None;
+                        let coordinate = self.track_coordinate(cx.mouse.cursorx, cx.mouse.cursory);
                         self.set_normalized_value_drag(
                             cx,
-                            remap_current_entity_x_coordinate(cx, cx.mouse.cursorx),
+                            self.remap_track_coordinate(cx, coordinate),
                         );
                     }
                 }
@@ -531,8 +807,10 @@ impl View for TimeSlider {
                     meta.consume();
                 }
             }
-            WindowEvent::MouseMove(x, _y) => {
+            WindowEvent::MouseMove(x, y) => {
                 if self.drag_active {
+                    let coordinate = self.track_coordinate(*x, *y);
+
                     // If shift is being held then the drag should be more granular instead of
                     // absolute
                     if cx.modifiers.shift() {
@@ -540,28 +818,28 @@ impl View for TimeSlider {
                             *self
                                 .granular_drag_status
                                 .get_or_insert_with(|| GranularDragStatus {
-                                    starting_x_coordinate: *x,
+                                    starting_coordinate: coordinate,
                                     starting_value: self.param_base.unmodulated_normalized_value(),
                                 });
 
                         // These positions should be compensated for the DPI scale so it remains
                         // consistent
-                        let start_x =
-                            remap_current_entity_x_t(cx, granular_drag_status.starting_value);
-                        let delta_x = ((*x - granular_drag_status.starting_x_coordinate)
+                        let start_coordinate =
+                            self.remap_track_t(cx, granular_drag_status.starting_value);
+                        let delta = ((coordinate - granular_drag_status.starting_coordinate)
                             * GRANULAR_DRAG_MULTIPLIER)
                             * cx.style.dpi_factor as f32;
 
                         self.set_normalized_value_drag(
                             cx,
-                            remap_current_entity_x_coordinate(cx, start_x + delta_x),
+                            self.remap_track_coordinate(cx, start_coordinate + delta),
                         );
                     } else {
                         self.granular_drag_status = None;
 
                         self.set_normalized_value_drag(
                             cx,
-                            remap_current_entity_x_coordinate(cx, *x),
+                            self.remap_track_coordinate(cx, coordinate),
                         );
                     }
                 }
@@ -571,12 +849,57 @@ impl View for TimeSlider {
                 // position
                 if self.drag_active && self.granular_drag_status.is_some() {
                     self.granular_drag_status = None;
-                    self.param_base.set_normalized_value(
-                        cx,
-                        remap_current_entity_x_coordinate(cx, cx.mouse.cursorx),
-                    );
+                    let coordinate = self.track_coordinate(cx.mouse.cursorx, cx.mouse.cursory);
+                    self.set_normalized_value_drag(cx, self.remap_track_coordinate(cx, coordinate));
                 }
             }
+            // The text box handles its own keyboard input
+            WindowEvent::KeyDown(_, Some(key)) if !self.text_input_active => {
+                // Shift allows for finer steps, just like with scrolling
+                let use_finer_steps = cx.modifiers.shift();
+                let current_value = self.param_base.unmodulated_normalized_value();
+
+                match key {
+                    Key::ArrowUp | Key::ArrowRight => {
+                        let next_value = self
+                            .param_base
+                            .next_normalized_step(current_value, use_finer_steps);
+                        self.set_normalized_value_gesture(cx, next_value);
+                    }
+                    Key::ArrowDown | Key::ArrowLeft => {
+                        let previous_value = self
+                            .param_base
+                            .previous_normalized_step(current_value, use_finer_steps);
+                        self.set_normalized_value_gesture(cx, previous_value);
+                    }
+                    Key::Home => self.set_normalized_value_gesture(cx, 0.0),
+                    Key::End => self.set_normalized_value_gesture(cx, 1.0),
+                    Key::Delete | Key::Backspace => {
+                        self.set_normalized_value_gesture(
+                            cx,
+                            self.param_base.default_normalized_value(),
+                        );
+                    }
+                    Key::Enter => {
+                        self.text_input_active = true;
+                        cx.set_active(true);
+                    }
+                    Key::Escape if self.drag_active => {
+                        // Cancelling a drag restores the value from before the drag
+                        self.drag_active = false;
+                        self.granular_drag_status = None;
+                        cx.release();
+                        cx.set_active(false);
+
+                        self.param_base
+                            .set_normalized_value(cx, self.drag_starting_value);
+                        self.param_base.end_set_parameter(cx);
+                    }
+                    _ => return,
+                }
+
+                meta.consume();
+            }
             WindowEvent::MouseScroll(_scroll_x, scroll_y) if self.use_scroll_wheel => {
                 // With a regular scroll wheel `scroll_y` will only ever be -1 or 1, but with smooth
                 // scrolling trackpads being a thing `scroll_y` could be anything.
@@ -629,9 +952,39 @@ pub trait TimeSliderExt {
     /// Change how the [`ParamSlider`] visualizes the current value.
     fn set_style(self, style: TimeSliderStyle) -> Self;
 
+    /// Change the direction the slider is dragged in. Vertical sliders work like faders.
+    fn set_orientation(self, orientation: TimeSliderOrientation) -> Self;
+
+    /// Don't draw the value on top of the slider. The value can still be entered with Alt+Click.
+    fn compact(self) -> Self;
+
+    /// Change how the parameter's value is displayed. The parameter's plain value is interpreted as
+    /// a time in seconds. This is a shorthand for [`with_formatter()`][Self::with_formatter()].
+    fn set_time_format(self, time_format: TimeFormat) -> Self;
+
+    /// Use a custom [`ValueFormatter`] for displaying the parameter's value and parsing text input,
+    /// for instance to display decibels or hertz instead of a time.
+    fn with_formatter(self, formatter: impl ValueFormatter) -> Self;
+
     /// Manually set a fixed label for the slider instead of displaying the current value. This is
-    /// currently not reactive.
+    /// not reactive, use [`with_label_lens()`][Self::with_label_lens()] for labels that change.
     fn with_label(self, value: impl Into<String>) -> Self;
+
+    /// Display the label from `label` instead of the current value, updating whenever the lens'
+    /// value changes. When the lens returns `None` the parameter's value is displayed as usual.
+    /// Useful for labels like `OFF` that depend on other state.
+    fn with_label_lens<L>(self, label: L) -> Self
+    where
+        L: Lens<Target = Option<String>>;
+
+    /// Snap to a grid of plain values while dragging, for instance note divisions at the current
+    /// tempo. Holding down Ctrl/Cmd while dragging temporarily disables snapping.
+    fn with_snap_grid(self, snap_grid: SnapGrid) -> Self;
+
+    /// Draw tick marks with labels at these plain values, e.g. `[0.0, 0.25, 0.5, 1.0, 2.0]` for a
+    /// time in seconds. The labels use the slider's formatter and are drawn outside of the slider,
+    /// so leave some space below horizontal sliders or to the right of vertical sliders.
+    fn with_ticks(self, plain_values: impl Into<Vec<f32>>) -> Self;
 }
 
 impl TimeSliderExt for Handle<'_, TimeSlider> {
@@ -640,7 +993,25 @@ impl TimeSliderExt for Handle<'_, TimeSlider> {
     }
 
     fn set_style(self, style: TimeSliderStyle) -> Self {
-        self.modify(|param_slider: &mut TimeSlider| param_slider.style = style)
+        self.modify(|param_slider: &mut TimeSlider| param_slider.layout.style = style)
+    }
+
+    fn set_orientation(self, orientation: TimeSliderOrientation) -> Self {
+        self.modify(|param_slider: &mut TimeSlider| param_slider.layout.orientation = orientation)
+    }
+
+    fn compact(self) -> Self {
+        self.modify(|param_slider: &mut TimeSlider| param_slider.layout.compact = true)
+    }
+
+    fn set_time_format(self, time_format: TimeFormat) -> Self {
+        self.with_formatter(time_format)
+    }
+
+    fn with_formatter(self, formatter: impl ValueFormatter) -> Self {
+        self.modify(|param_slider: &mut TimeSlider| {
+            param_slider.formatter = SharedFormatter::new(formatter)
+        })
     }
 
     fn with_label(self, value: impl Into<String>) -> Self {
@@ -648,4 +1019,23 @@ impl TimeSliderExt for Handle<'_, TimeSlider> {
             param_slider.label_override = Some(value.into())
         })
     }
+
+    fn with_label_lens<L>(self, label: L) -> Self
+    where
+        L: Lens<Target = Option<String>>,
+    {
+        self.bind(label, |handle, label| {
+            let label = label.get(&handle);
+            handle.modify(|param_slider: &mut TimeSlider| param_slider.label_override = label);
+        })
+    }
+
+    fn with_snap_grid(self, snap_grid: SnapGrid) -> Self {
+        self.modify(|param_slider: &mut TimeSlider| param_slider.snap_grid = Some(snap_grid))
+    }
+
+    fn with_ticks(self, plain_values: impl Into<Vec<f32>>) -> Self {
+        let plain_values = plain_values.into();
+        self.modify(|param_slider: &mut TimeSlider| param_slider.ticks = plain_values)
+    }
 }
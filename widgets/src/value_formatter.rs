@@ -0,0 +1,292 @@
+//! Pluggable conversion between parameter values and the text shown in widgets.
+
+use nih_plug::prelude::{util, Param, ParamPtr};
+use nih_plug_vizia::vizia::prelude::*;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Converts a parameter's value to the text displayed by a widget and parses text entered by the
+/// user back to a normalized value. Widgets like [`TimeSlider`][crate::time_slider::TimeSlider]
+/// accept a formatter through an extension method, so the same widget can show decibels, hertz,
+/// times, or completely custom labels.
+pub trait ValueFormatter: 'static {
+    /// Format the parameter's value at `normalized_value`.
+    fn format(&self, param: &FormatterParam, normalized_value: f32) -> String;
+
+    /// Parse a string entered by the user to a normalized value. Returns `None` if the string
+    /// can't be parsed, in which case the widget shows an error state.
+    fn parse(&self, param: &FormatterParam, string: &str) -> Option<f32>;
+}
+
+/// The parameter a [`ValueFormatter`] is formatting values for. Values are exposed as `f32`
+/// regardless of the parameter's type.
+pub struct FormatterParam<'a> {
+    param_ptr: ParamPtr,
+    _param: PhantomData<&'a ()>,
+}
+
+impl<'a> FormatterParam<'a> {
+    pub fn new<P: Param>(param: &'a P) -> Self {
+        Self {
+            param_ptr: param.as_ptr(),
+            _param: PhantomData,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The parameter `param_ptr` points to must outlive the returned object.
+    pub(crate) unsafe fn from_ptr(param_ptr: ParamPtr) -> Self {
+        Self {
+            param_ptr,
+            _param: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        unsafe { self.param_ptr.name() }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        unsafe { self.param_ptr.unit() }
+    }
+
+    pub fn step_count(&self) -> Option<usize> {
+        unsafe { self.param_ptr.step_count() }
+    }
+
+    pub fn unmodulated_normalized_value(&self) -> f32 {
+        unsafe { self.param_ptr.unmodulated_normalized_value() }
+    }
+
+    pub fn unmodulated_plain_value(&self) -> f32 {
+        unsafe { self.param_ptr.unmodulated_plain_value() }
+    }
+
+    /// Convert a normalized value to a plain value without changing the parameter.
+    pub fn preview_plain(&self, normalized: f32) -> f32 {
+        unsafe { self.param_ptr.preview_plain(normalized) }
+    }
+
+    /// Convert a plain value to a normalized value without changing the parameter.
+    pub fn preview_normalized(&self, plain: f32) -> f32 {
+        unsafe { self.param_ptr.preview_normalized(plain) }
+    }
+
+    /// The parameter's own string conversion.
+    pub fn normalized_value_to_string(&self, normalized: f32, include_unit: bool) -> String {
+        unsafe {
+            self.param_ptr
+                .normalized_value_to_string(normalized, include_unit)
+        }
+    }
+
+    /// The parameter's own string parsing.
+    pub fn string_to_normalized_value(&self, string: &str) -> Option<f32> {
+        unsafe { self.param_ptr.string_to_normalized_value(string) }
+    }
+}
+
+/// A [`ValueFormatter`] that can be stored in a widget and bound to through a lens. Two of these
+/// compare equal when they refer to the same formatter.
+#[derive(Clone)]
+pub struct SharedFormatter(pub Arc<dyn ValueFormatter>);
+
+impl SharedFormatter {
+    pub fn new(formatter: impl ValueFormatter) -> Self {
+        Self(Arc::new(formatter))
+    }
+}
+
+impl Data for SharedFormatter {
+    fn same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::ops::Deref for SharedFormatter {
+    type Target = dyn ValueFormatter;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+/// Uses the parameter's own value to string and string to value conversions, just like NIH-plug's
+/// `ParamSlider`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultFormatter;
+
+impl ValueFormatter for DefaultFormatter {
+    fn format(&self, param: &FormatterParam, normalized_value: f32) -> String {
+        param.normalized_value_to_string(normalized_value, true)
+    }
+
+    fn parse(&self, param: &FormatterParam, string: &str) -> Option<f32> {
+        param.string_to_normalized_value(string)
+    }
+}
+
+/// Displays a parameter with a linear gain as plain value in decibels, e.g. `-6.0 dB`.
+#[derive(Debug, Clone, Copy)]
+pub struct DecibelFormatter {
+    pub decimals: usize,
+}
+
+impl Default for DecibelFormatter {
+    fn default() -> Self {
+        Self { decimals: 1 }
+    }
+}
+
+impl ValueFormatter for DecibelFormatter {
+    fn format(&self, param: &FormatterParam, normalized_value: f32) -> String {
+        let gain = param.preview_plain(normalized_value);
+        if gain <= util::MINUS_INFINITY_GAIN {
+            String::from("-inf dB")
+        } else {
+            format!("{:.*} dB", self.decimals, util::gain_to_db(gain))
+        }
+    }
+
+    fn parse(&self, param: &FormatterParam, string: &str) -> Option<f32> {
+        let string = string.trim();
+        let string = string
+            .strip_suffix("dB")
+            .or_else(|| string.strip_suffix("db"))
+            .unwrap_or(string)
+            .trim();
+        let gain = if string.eq_ignore_ascii_case("-inf") {
+            0.0
+        } else {
+            util::db_to_gain(string.parse().ok()?)
+        };
+
+        Some(param.preview_normalized(gain))
+    }
+}
+
+/// Displays a frequency in hertz as plain value, switching to kilohertz from 1000 Hz, e.g.
+/// `440 Hz` or `2.50 kHz`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HertzFormatter;
+
+impl ValueFormatter for HertzFormatter {
+    fn format(&self, param: &FormatterParam, normalized_value: f32) -> String {
+        let frequency = param.preview_plain(normalized_value);
+        if frequency >= 1000.0 {
+            format!("{:.2} kHz", frequency / 1000.0)
+        } else {
+            format!("{frequency:.0} Hz")
+        }
+    }
+
+    fn parse(&self, param: &FormatterParam, string: &str) -> Option<f32> {
+        let string = string.trim().to_lowercase();
+        let frequency = if let Some(kilohertz) = string
+            .strip_suffix("khz")
+            .or_else(|| string.strip_suffix('k'))
+        {
+            kilohertz.trim().parse::<f32>().ok()? * 1000.0
+        } else {
+            string
+                .strip_suffix("hz")
+                .unwrap_or(&string)
+                .trim()
+                .parse()
+                .ok()?
+        };
+
+        Some(param.preview_normalized(frequency))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nih_plug::prelude::{FloatParam, FloatRange};
+
+    fn assert_parses(
+        formatter: &impl ValueFormatter,
+        param: &FloatParam,
+        string: &str,
+        expected_plain: Option<f32>,
+    ) {
+        let param = FormatterParam::new(param);
+        let plain = formatter
+            .parse(&param, string)
+            .map(|normalized| param.preview_plain(normalized));
+        match (plain, expected_plain) {
+            (Some(plain), Some(expected)) => assert!(
+                (plain - expected).abs() <= 1e-4 * expected.abs().max(1.0),
+                "'{string}' parsed as {plain} instead of {expected}"
+            ),
+            (plain, expected) => assert_eq!(plain, expected, "'{string}'"),
+        }
+    }
+
+    fn format(formatter: &impl ValueFormatter, param: &FloatParam, plain: f32) -> String {
+        let param = FormatterParam::new(param);
+        formatter.format(&param, param.preview_normalized(plain))
+    }
+
+    #[test]
+    fn decibels() {
+        let gain = FloatParam::new("Gain", 1.0, FloatRange::Linear { min: 0.0, max: 2.0 });
+        let formatter = DecibelFormatter::default();
+
+        assert_eq!(format(&formatter, &gain, 1.0), "0.0 dB");
+        assert_eq!(format(&formatter, &gain, 0.5), "-6.0 dB");
+        assert_eq!(format(&formatter, &gain, 0.0), "-inf dB");
+        assert_eq!(
+            format(&DecibelFormatter { decimals: 2 }, &gain, 2.0),
+            "6.02 dB"
+        );
+
+        for (string, expected) in [
+            ("0 dB", Some(1.0)),
+            ("-6dB", Some(util::db_to_gain(-6.0))),
+            (" 6.02 db ", Some(util::db_to_gain(6.02))),
+            ("-6", Some(util::db_to_gain(-6.0))),
+            ("-inf", Some(0.0)),
+            ("-INF dB", Some(0.0)),
+            ("", None),
+            ("dB", None),
+            ("abc", None),
+        ] {
+            assert_parses(&formatter, &gain, string, expected);
+        }
+    }
+
+    #[test]
+    fn hertz() {
+        let frequency = FloatParam::new(
+            "Frequency",
+            440.0,
+            FloatRange::Linear {
+                min: 20.0,
+                max: 20_000.0,
+            },
+        );
+        let formatter = HertzFormatter;
+
+        assert_eq!(format(&formatter, &frequency, 440.0), "440 Hz");
+        assert_eq!(format(&formatter, &frequency, 999.0), "999 Hz");
+        assert_eq!(format(&formatter, &frequency, 1000.0), "1.00 kHz");
+        assert_eq!(format(&formatter, &frequency, 2500.0), "2.50 kHz");
+
+        for (string, expected) in [
+            ("440 Hz", Some(440.0)),
+            ("440hz", Some(440.0)),
+            ("440", Some(440.0)),
+            ("2.5 kHz", Some(2500.0)),
+            ("2.5KHZ", Some(2500.0)),
+            ("2.5k", Some(2500.0)),
+            ("", None),
+            ("kHz", None),
+            ("abc Hz", None),
+        ] {
+            assert_parses(&formatter, &frequency, string, expected);
+        }
+    }
+}